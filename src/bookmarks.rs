@@ -0,0 +1,111 @@
+use crate::errors::ConfigError;
+use crate::storage::{data_dir, replace_file};
+use crate::zones::Zone;
+use chrono::{DateTime, FixedOffset, Offset};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Named datetimes saved with `since mark`, which can be used in place of any input.
+///
+/// Stored in `~/.local/share/since/bookmarks` by default, one bookmark per line with the name and
+/// an RFC 3339 datetime separated by a tab.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bookmarks {
+    marks: BTreeMap<String, DateTime<FixedOffset>>,
+}
+
+impl Bookmarks {
+    /// Datetime saved under the name, in the given timezone.
+    pub fn get(&self, name: &str, zone: &Zone) -> Option<DateTime<Zone>> {
+        self.marks
+            .get(name)
+            .map(|datetime| datetime.with_timezone(zone))
+    }
+
+    /// Save the datetime under the name, replacing any previous one.
+    pub fn insert(&mut self, name: &str, datetime: DateTime<Zone>) {
+        let offset = datetime.offset().fix();
+        self.marks
+            .insert(name.to_string(), datetime.with_timezone(&offset));
+    }
+
+    /// Remove the bookmark, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.marks.remove(name).is_some()
+    }
+
+    /// Move the bookmark to a new name. Returns `false` if there's nothing to move or the new name
+    /// is already taken.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        if self.marks.contains_key(to) {
+            return false;
+        }
+
+        match self.marks.remove(from) {
+            Some(datetime) => {
+                self.marks.insert(to.to_string(), datetime);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// All the bookmark names, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.marks.keys().map(String::as_str)
+    }
+
+    /// Contents of the bookmarks file, readable by `parse_bookmarks`.
+    pub fn to_file(&self) -> String {
+        self.marks
+            .iter()
+            .map(|(name, datetime)| format!("{}\t{}\n", name, datetime.to_rfc3339()))
+            .collect()
+    }
+}
+
+/// Location of the bookmarks file.
+pub fn bookmarks_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("bookmarks"))
+}
+
+/// Read the bookmarks from the given path. A missing file is the same as an empty one.
+pub fn load_bookmarks(path: &Path) -> Result<Bookmarks, ConfigError> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse_bookmarks(&text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Bookmarks::default()),
+        Err(err) => Err(ConfigError::Io(err)),
+    }
+}
+
+/// Write the bookmarks into the given path, see `replace_file`.
+pub fn save_bookmarks(path: &Path, bookmarks: &Bookmarks) -> io::Result<()> {
+    replace_file(path, &bookmarks.to_file())
+}
+
+/// Parse the contents of a bookmarks file.
+pub fn parse_bookmarks(text: &str) -> Result<Bookmarks, ConfigError> {
+    let mut bookmarks = Bookmarks::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let syntax_error = |reason: &str| ConfigError::Syntax {
+            line: index + 1,
+            reason: reason.to_string(),
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (name, datetime) = line
+            .split_once('\t')
+            .ok_or_else(|| syntax_error("expected a name and a datetime separated by a tab"))?;
+        let datetime = DateTime::parse_from_rfc3339(datetime.trim())
+            .map_err(|err| syntax_error(&format!("invalid datetime: {}", err)))?;
+
+        bookmarks.marks.insert(name.to_string(), datetime);
+    }
+
+    Ok(bookmarks)
+}
@@ -1,17 +1,488 @@
-use chrono::{DateTime, Datelike, Local};
+use crate::formatters::{get_iso_output, get_output, OutputOptions};
+use crate::subcommands::Filter;
+use crate::zones::Zone;
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+use std::convert::TryFrom;
+use std::ops::Neg;
 
-pub fn calculate_month_diff(from: DateTime<Local>, to: DateTime<Local>) -> i64 {
-    // Individual typecasting is necessary to
-    // a) compile at all
-    // b) not panic from subtraction overflow
-    let from_month = from.month() as i32;
-    let to_month = to.month() as i32;
+/// Length of an average month in the Gregorian calendar, 365.2425 / 12 days.
+const AVERAGE_MONTH_SECONDS: i64 = 2_629_746;
 
-    // TODO: Figure out if we want a more precise formula here.
-    ((from.year() - to.year()) * 12 + from_month - to_month).abs() as i64
+/// Time difference split into calendar-aware units, eg. "1 year, 2 months and 3 days".
+///
+/// Also used as a duration to move datetimes by, see `add_breakdown`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Breakdown {
+    pub years: i64,
+    pub months: i64,
+    pub days: i64,
+    pub hours: i64,
+    pub minutes: i64,
+    pub seconds: i64,
 }
 
-pub fn calculate_year_diff(from: DateTime<Local>, to: DateTime<Local>) -> i64 {
-    // TODO: Figure out if we want a more precise formula here.
-    (from.year() - to.year()).abs() as i64
+impl Neg for Breakdown {
+    type Output = Breakdown;
+
+    fn neg(self) -> Breakdown {
+        Breakdown {
+            years: -self.years,
+            months: -self.months,
+            days: -self.days,
+            hours: -self.hours,
+            minutes: -self.minutes,
+            seconds: -self.seconds,
+        }
+    }
+}
+
+/// Time difference between two datetimes, with accessors for all the different units.
+///
+/// All values are signed: negative if `to` is before `from`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+}
+
+impl Difference {
+    pub fn new(from: DateTime<Zone>, to: DateTime<Zone>) -> Difference {
+        Difference { from, to }
+    }
+
+    pub fn from(&self) -> DateTime<Zone> {
+        self.from
+    }
+
+    pub fn to(&self) -> DateTime<Zone> {
+        self.to
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.from > self.to
+    }
+
+    /// Exact duration between the datetimes.
+    pub fn duration(&self) -> Duration {
+        self.to.signed_duration_since(self.from)
+    }
+
+    /// Full calendar years, see `calculate_year_diff`.
+    pub fn years(&self) -> i64 {
+        self.sign() * calculate_year_diff(self.from, self.to)
+    }
+
+    /// Full calendar months, see `calculate_month_diff`.
+    pub fn months(&self) -> i64 {
+        self.sign() * calculate_month_diff(self.from, self.to)
+    }
+
+    pub fn weeks(&self) -> i64 {
+        self.duration().num_weeks()
+    }
+
+    pub fn days(&self) -> i64 {
+        self.duration().num_days()
+    }
+
+    pub fn hours(&self) -> i64 {
+        self.duration().num_hours()
+    }
+
+    pub fn minutes(&self) -> i64 {
+        self.duration().num_minutes()
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.duration().num_seconds()
+    }
+
+    pub fn milliseconds(&self) -> i64 {
+        self.duration().num_milliseconds()
+    }
+
+    /// Whole microseconds, which don't fit in an `i64` for every duration.
+    pub fn microseconds(&self) -> i128 {
+        duration_as_nanoseconds(self.duration()) / 1_000
+    }
+
+    /// Whole nanoseconds, see `duration_as_nanoseconds`.
+    pub fn nanoseconds(&self) -> i128 {
+        duration_as_nanoseconds(self.duration())
+    }
+
+    /// Absolute distance split into calendar-aware units, see `calculate_breakdown`.
+    pub fn breakdown(&self) -> Breakdown {
+        calculate_breakdown(self.from, self.to)
+    }
+
+    /// Absolute distance in the same guessed shorthand format as `since` prints by default, eg.
+    /// "3 hours and 12 minutes".
+    pub fn shorthand(&self) -> String {
+        get_output(self.from, self.to, Filter::None, &OutputOptions::default())
+    }
+
+    /// Signed ISO 8601 duration of the calendar breakdown, eg. "P1Y2M3DT4H5M6S" or "-P3D".
+    pub fn iso8601(&self) -> String {
+        let signed = OutputOptions {
+            signed: true,
+            ..OutputOptions::default()
+        };
+        get_iso_output(self.from, self.to, &signed)
+    }
+
+    fn sign(&self) -> i64 {
+        if self.is_negative() {
+            -1
+        } else {
+            1
+        }
+    }
+}
+
+/// Business days and hours, for counting only the time that's actually worked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkCalendar {
+    /// Dates that aren't worked even though they're on a weekday.
+    pub holidays: Vec<NaiveDate>,
+    /// Start of the working day.
+    pub start: NaiveTime,
+    /// End of the working day, after `start`.
+    pub end: NaiveTime,
+}
+
+impl Default for WorkCalendar {
+    /// Monday to Friday, 09:00-17:00.
+    fn default() -> WorkCalendar {
+        WorkCalendar {
+            holidays: vec![],
+            start: NaiveTime::from_hms(9, 0, 0),
+            end: NaiveTime::from_hms(17, 0, 0),
+        }
+    }
+}
+
+impl WorkCalendar {
+    /// Whether the date is a weekday that isn't a holiday.
+    pub fn is_workday(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// Length of a single working day.
+    pub fn day_length(&self) -> Duration {
+        self.end.signed_duration_since(self.start)
+    }
+}
+
+/// Sum up the working hours between the two datetimes, skipping nights, weekends and holidays.
+///
+/// Always positive, like the other calculations. Divide by `WorkCalendar::day_length` for
+/// working days.
+pub fn calculate_work_duration(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    calendar: &WorkCalendar,
+) -> Duration {
+    let (start, end) = local_span(from, to);
+
+    let mut total = Duration::zero();
+    let mut date = start.date();
+
+    while date <= end.date() {
+        if calendar.is_workday(date) {
+            let day_start = start.max(date.and_time(calendar.start));
+            let day_end = end.min(date.and_time(calendar.end));
+
+            if day_start < day_end {
+                total = total + day_end.signed_duration_since(day_start);
+            }
+        }
+        date = date.succ();
+    }
+
+    total
+}
+
+/// Count the full calendar months between the two datetimes.
+///
+/// A month is only counted once the same day and time of month has been reached again, eg.
+/// 2019-12-31 -> 2020-01-01 is 0 months. See `add_months` for how month ends are handled.
+pub fn calculate_month_diff(from: DateTime<Zone>, to: DateTime<Zone>) -> i64 {
+    let (start, end) = local_span(from, to);
+    count_whole_months(start, end)
+}
+
+/// Count the full calendar years between the two datetimes, ie. the number of passed anniversaries.
+pub fn calculate_year_diff(from: DateTime<Zone>, to: DateTime<Zone>) -> i64 {
+    calculate_month_diff(from, to) / 12
+}
+
+/// Calculate the months between the two datetimes, including the passed fraction of the current
+/// month.
+///
+/// The fraction is relative to the real length of the month being counted, so eg. 14 days in
+/// February is a bigger fraction than 14 days in March.
+pub fn calculate_fractional_month_diff(from: DateTime<Zone>, to: DateTime<Zone>) -> f64 {
+    calculate_fractional_diff(from, to, 1)
+}
+
+/// Calculate the years between the two datetimes, including the passed fraction of the current year.
+pub fn calculate_fractional_year_diff(from: DateTime<Zone>, to: DateTime<Zone>) -> f64 {
+    calculate_fractional_diff(from, to, 12)
+}
+
+/// Count the full periods of `step` months between the datetimes and add the fraction of the
+/// period that has passed after the last full one.
+fn calculate_fractional_diff(from: DateTime<Zone>, to: DateTime<Zone>, step: i64) -> f64 {
+    let (start, end) = local_span(from, to);
+
+    let periods = count_whole_months(start, end) / step;
+    let period_start = add_months(start, periods * step).expect("start of a passed period");
+    let length = match add_months(start, (periods + 1) * step) {
+        Some(period_end) => period_end.signed_duration_since(period_start),
+        // The period ends past the last supported date, so settle for an average length.
+        None => Duration::seconds(step * AVERAGE_MONTH_SECONDS),
+    };
+
+    let passed = duration_as_seconds(end.signed_duration_since(period_start));
+    let length = duration_as_seconds(length);
+
+    periods as f64 + passed / length
+}
+
+/// Convert the duration into seconds, keeping the sub-second precision.
+pub fn duration_as_seconds(duration: Duration) -> f64 {
+    match duration.num_nanoseconds() {
+        Some(nanos) => nanos as f64 / 1e9,
+        // Nanoseconds overflow after a couple hundred years, milliseconds are plenty precise there.
+        None => duration.num_milliseconds() as f64 / 1e3,
+    }
+}
+
+/// Convert the duration into whole nanoseconds, which unlike `num_nanoseconds` doesn't overflow.
+pub fn duration_as_nanoseconds(duration: Duration) -> i128 {
+    let seconds = duration.num_seconds();
+    // The remainder is always under a second, so it fits.
+    let nanos = (duration - Duration::seconds(seconds))
+        .num_nanoseconds()
+        .unwrap_or(0);
+
+    seconds as i128 * 1_000_000_000 + nanos as i128
+}
+
+/// Split the distance between the two datetimes into years, months, days, hours, minutes and
+/// seconds.
+///
+/// Years and months are counted on the calendar from the earlier datetime onwards, the rest is
+/// plain duration arithmetic on whatever remains after them.
+pub fn calculate_breakdown(from: DateTime<Zone>, to: DateTime<Zone>) -> Breakdown {
+    let (start, end) = local_span(from, to);
+
+    let months = count_whole_months(start, end);
+    let remainder =
+        end.signed_duration_since(add_months(start, months).expect("end of the passed months"));
+
+    Breakdown {
+        years: months / 12,
+        months: months % 12,
+        days: remainder.num_days(),
+        hours: remainder.num_hours() % 24,
+        minutes: remainder.num_minutes() % 60,
+        seconds: remainder.num_seconds() % 60,
+    }
+}
+
+/// Split the absolute time difference into only the given units, in the same order. The largest
+/// unit absorbs everything above it, eg. 1 day and 3 hours is 27 hours when days aren't included.
+///
+/// Like in `calculate_breakdown`, years and months are counted on the calendar.
+pub fn calculate_units(from: DateTime<Zone>, to: DateTime<Zone>, units: &[Filter]) -> Vec<i64> {
+    let (start, end) = local_span(from, to);
+    let has = |unit: Filter| units.contains(&unit);
+
+    let months = if has(Filter::Years) || has(Filter::Months) {
+        count_whole_months(start, end)
+    } else {
+        0
+    };
+    let years = if has(Filter::Years) { months / 12 } else { 0 };
+    let months = if has(Filter::Months) {
+        months - years * 12
+    } else {
+        0
+    };
+
+    let mut remainder = end
+        .signed_duration_since(
+            add_months(start, years * 12 + months).expect("end of the passed months"),
+        )
+        .num_seconds();
+    let mut take = |unit: Filter, seconds: i64| {
+        if has(unit) {
+            let value = remainder / seconds;
+            remainder -= value * seconds;
+            value
+        } else {
+            0
+        }
+    };
+
+    let weeks = take(Filter::Weeks, 60 * 60 * 24 * 7);
+    let days = take(Filter::Days, 60 * 60 * 24);
+    let hours = take(Filter::Hours, 60 * 60);
+    let minutes = take(Filter::Minutes, 60);
+    let seconds = take(Filter::Seconds, 1);
+
+    units
+        .iter()
+        .map(|unit| match unit {
+            Filter::Years => years,
+            Filter::Months => months,
+            Filter::Weeks => weeks,
+            Filter::Days => days,
+            Filter::Hours => hours,
+            Filter::Minutes => minutes,
+            Filter::Seconds => seconds,
+            Filter::Milliseconds
+            | Filter::Microseconds
+            | Filter::Nanoseconds
+            | Filter::Full
+            | Filter::Clock
+            | Filter::Workdays
+            | Filter::Workhours
+            | Filter::None => 0,
+        })
+        .collect()
+}
+
+/// Move the datetime by every unit of the breakdown, from years down to seconds.
+///
+/// Works the same way as `shift_datetime`: years, months and days follow the calendar, the rest
+/// are exact durations. Returns `None` if the result is out of range or doesn't exist locally.
+pub fn add_breakdown(datetime: DateTime<Zone>, breakdown: &Breakdown) -> Option<DateTime<Zone>> {
+    let months = breakdown
+        .years
+        .checked_mul(12)?
+        .checked_add(breakdown.months)?;
+    let shifted = add_months(datetime.naive_local(), months)?
+        .checked_add_signed(checked_seconds(breakdown.days, 60 * 60 * 24)?)?;
+
+    datetime
+        .timezone()
+        .from_local_datetime(&shifted)
+        .earliest()?
+        .checked_add_signed(checked_seconds(breakdown.hours, 60 * 60)?)?
+        .checked_add_signed(checked_seconds(breakdown.minutes, 60)?)?
+        .checked_add_signed(checked_seconds(breakdown.seconds, 1)?)
+}
+
+/// Move the datetime by the given amount of units, eg. -3 days.
+///
+/// Days and longer units follow the calendar, so that "1 day" across a DST change is still the same
+/// time of day. Returns `None` for the non-unit filters, or if the result doesn't exist locally.
+pub fn shift_datetime(
+    datetime: DateTime<Zone>,
+    amount: i64,
+    unit: Filter,
+) -> Option<DateTime<Zone>> {
+    let naive = datetime.naive_local();
+
+    let shifted = match unit {
+        Filter::Years => add_months(naive, amount.checked_mul(12)?)?,
+        Filter::Months => add_months(naive, amount)?,
+        Filter::Weeks => naive.checked_add_signed(checked_seconds(amount, 60 * 60 * 24 * 7)?)?,
+        Filter::Days => naive.checked_add_signed(checked_seconds(amount, 60 * 60 * 24)?)?,
+        Filter::Hours => return datetime.checked_add_signed(checked_seconds(amount, 60 * 60)?),
+        Filter::Minutes => return datetime.checked_add_signed(checked_seconds(amount, 60)?),
+        Filter::Seconds => return datetime.checked_add_signed(checked_seconds(amount, 1)?),
+        Filter::Milliseconds => return datetime.checked_add_signed(Duration::milliseconds(amount)),
+        Filter::Microseconds => return datetime.checked_add_signed(Duration::microseconds(amount)),
+        Filter::Nanoseconds => return datetime.checked_add_signed(Duration::nanoseconds(amount)),
+        Filter::Full | Filter::Clock | Filter::Workdays | Filter::Workhours | Filter::None => {
+            return None
+        }
+    };
+
+    datetime.timezone().from_local_datetime(&shifted).earliest()
+}
+
+/// Duration of `amount` units of the given length in seconds, or `None` if it's too long for
+/// `Duration`, which would otherwise panic.
+fn checked_seconds(amount: i64, unit: i64) -> Option<Duration> {
+    amount
+        .checked_mul(unit)?
+        .checked_mul(1000)
+        .map(Duration::milliseconds)
+}
+
+/// How long to wait from `now` until the next whole `interval` counted from `target`, in either
+/// direction. Keeps a live countdown ticking on the same fraction of a second as its target, so
+/// that it hits zero exactly when the target is reached.
+pub fn until_next_tick(
+    now: DateTime<Zone>,
+    target: DateTime<Zone>,
+    interval: Duration,
+) -> Duration {
+    let elapsed = now.signed_duration_since(target);
+
+    match (interval.num_nanoseconds(), elapsed.num_nanoseconds()) {
+        (Some(interval), Some(elapsed)) if interval > 0 => {
+            Duration::nanoseconds(interval - elapsed.rem_euclid(interval))
+        }
+        // Centuries away from the target, so a tick or two off doesn't matter.
+        _ => interval,
+    }
+}
+
+/// Move the datetime by the given amount of calendar months.
+///
+/// Days that don't exist in the target month are clamped to its last day, eg. Jan 31st + 1 month
+/// results in Feb 28th (or 29th on leap years). Returns `None` if the result is out of range.
+pub fn add_months(datetime: NaiveDateTime, months: i64) -> Option<NaiveDateTime> {
+    let total = (datetime.year() as i64 * 12 + datetime.month0() as i64).checked_add(months)?;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = total.rem_euclid(12) as u32 + 1;
+
+    // The last day of the month that exists, if the original one doesn't.
+    (1..=datetime.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .map(|date| date.and_time(datetime.time()))
+}
+
+/// Local datetimes of the span between the two datetimes, the earlier one first. Both are on the
+/// wall clock of `from`, so that the calendar math works even if `to` is in another timezone.
+fn local_span(from: DateTime<Zone>, to: DateTime<Zone>) -> (NaiveDateTime, NaiveDateTime) {
+    let to = to.with_timezone(&from.timezone());
+
+    if from <= to {
+        (from.naive_local(), to.naive_local())
+    } else {
+        (to.naive_local(), from.naive_local())
+    }
+}
+
+/// Count how many full calendar months fit between `start` and `end`, where `start <= end`.
+fn count_whole_months(start: NaiveDateTime, end: NaiveDateTime) -> i64 {
+    let mut months =
+        (end.year() - start.year()) as i64 * 12 + end.month() as i64 - start.month() as i64;
+
+    // The naive month count is at most one too big, when the day or time hasn't been reached yet.
+    if months > 0 && !matches!(add_months(start, months), Some(date) if date <= end) {
+        months -= 1;
+    }
+
+    months
+}
+
+pub(crate) fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+
+    NaiveDate::from_ymd(next_year, next_month, 1).pred().day()
 }
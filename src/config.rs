@@ -0,0 +1,219 @@
+use crate::errors::ConfigError;
+use crate::parsers::{try_parse_with_options, ParseOptions};
+use crate::zones::Zone;
+use chrono::{DateTime, NaiveDate};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+/// Settings from the config file, `~/.config/since/config.toml` by default:
+///
+/// ```toml
+/// # Extra strftime patterns for inputs
+/// input_formats = ["%d%m%Y-%H%M", "IMG_%Y%m%d_%H%M%S.jpg"]
+/// # Whether to try them "before" or "after" the built-in formats
+/// input_formats_position = "before"
+/// ```
+///
+/// Only plain `key = value` pairs with strings and arrays of strings are supported, which is all
+/// of TOML that's needed here.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Extra strftime formats for parsing inputs.
+    pub input_formats: Vec<String>,
+    /// Try `input_formats` before the built-in formats instead of after them.
+    pub input_formats_first: bool,
+}
+
+/// Location of the config file: `$SINCE_CONFIG` if set, otherwise `since/config.toml` in
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("SINCE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("since").join("config.toml"))
+}
+
+/// Read the config file from the given path. A missing file is the same as an empty one.
+pub fn load_config(path: &Path) -> Result<Config, ConfigError> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse_config(&text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(ConfigError::Io(err)),
+    }
+}
+
+/// Parse the contents of a config file.
+pub fn parse_config(text: &str) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    let mut lines = text.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let syntax_error = |reason: &str| ConfigError::Syntax {
+            line: line_number,
+            reason: reason.to_string(),
+        };
+
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| syntax_error("expected `key = value`"))?;
+        let mut value = value.trim().to_string();
+
+        // Arrays can be split over multiple lines.
+        while value.starts_with('[') && !value.ends_with(']') {
+            match lines.next() {
+                Some((_, line)) => value.push_str(strip_comment(line).trim()),
+                None => return Err(syntax_error("unclosed array")),
+            }
+        }
+
+        match key.trim() {
+            "input_formats" => {
+                config.input_formats = parse_array(&value)
+                    .ok_or_else(|| syntax_error("expected an array of strings"))?
+            }
+            "input_formats_position" => {
+                config.input_formats_first = match parse_string(&value).as_deref() {
+                    Some("before") => true,
+                    Some("after") => false,
+                    _ => return Err(syntax_error("expected \"before\" or \"after\"")),
+                }
+            }
+            key => return Err(syntax_error(&format!("unknown key `{}`", key))),
+        }
+    }
+
+    Ok(config)
+}
+
+/// Read a list of holidays from the given path, see `parse_holidays`.
+pub fn load_holidays(
+    path: &Path,
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Result<Vec<NaiveDate>, ConfigError> {
+    let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+    parse_holidays(&text, now, options)
+}
+
+/// Parse a list of holidays, one date per line in any of the supported formats:
+///
+/// ```text
+/// 2020-12-24  # Christmas Eve
+/// 25.12.2020
+/// ```
+///
+/// Blank lines and `# comments` are ignored.
+pub fn parse_holidays(
+    text: &str,
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Result<Vec<NaiveDate>, ConfigError> {
+    let mut holidays = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match try_parse_with_options(line, now, options) {
+            Ok(datetime) => holidays.push(datetime.naive_local().date()),
+            Err(err) => {
+                return Err(ConfigError::Syntax {
+                    line: index + 1,
+                    reason: err.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(holidays)
+}
+
+/// Remove a trailing `# comment`, leaving any `#` inside strings alone.
+fn strip_comment(line: &str) -> &str {
+    match unquoted_chars(line).find(|(_, c)| *c == '#') {
+        Some((index, _)) => &line[..index],
+        None => line,
+    }
+}
+
+/// Iterate over the characters that aren't inside a string.
+fn unquoted_chars(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+
+    text.char_indices().filter(move |(_, c)| {
+        match (quote, *c) {
+            (Some('"'), _) if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(*c),
+            (None, _) => return true,
+        }
+        false
+    })
+}
+
+/// Parse a single basic (`"..."`) or literal (`'...'`) string.
+fn parse_string(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if let Some(literal) = value.strip_prefix('\'') {
+        return literal.strip_suffix('\'').map(String::from);
+    }
+
+    let basic = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut string = String::new();
+    let mut chars = basic.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => string.push('\\'),
+                '"' => string.push('"'),
+                't' => string.push('\t'),
+                _ => return None,
+            },
+            c => string.push(c),
+        }
+    }
+
+    Some(string)
+}
+
+/// Parse an array of strings, allowing a trailing comma.
+fn parse_array(value: &str) -> Option<Vec<String>> {
+    let items = value.strip_prefix('[')?.strip_suffix(']')?.trim();
+
+    split_items(items)
+        .into_iter()
+        .filter(|item| !item.trim().is_empty())
+        .map(parse_string)
+        .collect()
+}
+
+/// Split array items on commas outside of strings.
+fn split_items(items: &str) -> Vec<&str> {
+    let mut start = 0;
+    let mut result = vec![];
+
+    for (index, _) in unquoted_chars(items).filter(|(_, c)| *c == ',') {
+        result.push(&items[start..index]);
+        start = index + 1;
+    }
+
+    result.push(&items[start..]);
+    result
+}
@@ -0,0 +1,424 @@
+use crate::calculators::days_in_month;
+use crate::errors::ParseError;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use strsim::levenshtein;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// All the words understood in relative expressions, for typo suggestions.
+const KEYWORDS: [&str; 27] = [
+    "now",
+    "today",
+    "yesterday",
+    "tomorrow",
+    "noon",
+    "midnight",
+    "last",
+    "next",
+    "ago",
+    "from",
+    "in",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+    "years",
+    "months",
+    "weeks",
+    "days",
+    "hours",
+    "minutes",
+    "seconds",
+    "a",
+    "an",
+];
+
+/// Single `%X` field of a format, as found in the input.
+struct Field {
+    spec: char,
+    start: usize,
+    end: usize,
+}
+
+/// How far the input got when matched against a single format.
+enum Outcome {
+    /// The input stopped matching the format at this position.
+    Mismatch { position: usize, expected: String },
+    /// The input has the same shape as the format, with these fields.
+    Matched(Vec<Field>),
+}
+
+/// Figure out why the input didn't match any of the formats, by finding the format that matched
+/// the longest part of it and checking what went wrong there.
+pub(crate) fn diagnose(input: &str, formats: &[&str]) -> ParseError {
+    let mut closest: Option<(usize, &str, Outcome)> = None;
+
+    for format in formats {
+        let outcome = match_format(input, format);
+        let score = match outcome {
+            Outcome::Mismatch { position, .. } => position,
+            // Having the right shape always beats running into a wrong character.
+            Outcome::Matched(_) => input.len() + 1,
+        };
+
+        if !matches!(&closest, Some((best, _, _)) if *best >= score) {
+            closest = Some((score, format, outcome));
+        }
+    }
+
+    let error = ParseError {
+        input: input.to_string(),
+        closest_format: None,
+        position: 0,
+        length: input.len(),
+        reason: String::from("input doesn't match any supported format"),
+        suggestion: None,
+    };
+
+    match closest {
+        Some((score, format, outcome)) if score > 0 => {
+            let error = ParseError {
+                closest_format: Some(describe_format(format)),
+                ..error
+            };
+
+            match outcome {
+                // Words that don't fit anywhere are more likely typos in relative expressions.
+                Outcome::Mismatch { position, expected } => {
+                    suggest_keyword(input).unwrap_or(ParseError {
+                        position,
+                        length: input[position..].chars().next().map_or(0, char::len_utf8),
+                        reason: format!("expected {}", expected),
+                        ..error
+                    })
+                }
+                Outcome::Matched(fields) => match check_fields(&fields, input) {
+                    Some((field, reason, suggestion)) => ParseError {
+                        position: field.start,
+                        length: field.end - field.start,
+                        reason,
+                        suggestion,
+                        ..error
+                    },
+                    // Valid on its own, but skipped over in the timezone, eg. by a DST change.
+                    None if is_valid_local(input, format) => ParseError {
+                        reason: String::from("the time doesn't exist in the timezone"),
+                        ..error
+                    },
+                    None => ParseError {
+                        reason: String::from("the values don't form a valid date"),
+                        ..error
+                    },
+                },
+            }
+        }
+        _ => suggest_keyword(input).unwrap_or(error),
+    }
+}
+
+/// Walk through the format and input side by side, roughly the same way chrono does.
+fn match_format(input: &str, format: &str) -> Outcome {
+    let format = format.replace("%T", "%H:%M:%S").replace("%R", "%H:%M");
+    let mut specs = format.chars();
+    let mut position = 0;
+    let mut fields = vec![];
+
+    while let Some(spec) = specs.next() {
+        let rest = &input[position..];
+
+        match spec {
+            '%' => match specs.next() {
+                // Optional fraction of a second
+                Some('.') => {
+                    specs.next();
+                    let digits = count_digits(rest.strip_prefix('.').unwrap_or(""), 9);
+                    if digits > 0 {
+                        position += 1 + digits;
+                    }
+                }
+                Some(spec) => {
+                    let length = match spec {
+                        'B' | 'b' | 'h' | 'A' | 'a' | 'p' | 'P' => rest
+                            .chars()
+                            .take_while(|c| c.is_alphabetic())
+                            .map(char::len_utf8)
+                            .sum(),
+                        'z' => rest
+                            .chars()
+                            .take(6)
+                            .take_while(|c| "+-:".contains(*c) || c.is_ascii_digit())
+                            .count(),
+                        '%' => rest.starts_with('%') as usize,
+                        's' => count_digits(rest, 20),
+                        'Y' | 'G' => count_digits(rest, 4),
+                        'j' => count_digits(rest, 3),
+                        'u' => count_digits(rest, 1),
+                        _ => count_digits(rest, 2),
+                    };
+
+                    if length == 0 {
+                        return Outcome::Mismatch {
+                            position,
+                            expected: describe_spec(spec).to_string(),
+                        };
+                    }
+
+                    fields.push(Field {
+                        spec,
+                        start: position,
+                        end: position + length,
+                    });
+                    position += length;
+                }
+                None => break,
+            },
+            ' ' => position += rest.len() - rest.trim_start().len(),
+            literal => {
+                if !rest.starts_with(literal) {
+                    return Outcome::Mismatch {
+                        position,
+                        expected: format!("`{}`", literal),
+                    };
+                }
+                position += literal.len_utf8();
+            }
+        }
+    }
+
+    if position < input.len() {
+        return Outcome::Mismatch {
+            position,
+            expected: String::from("end of input"),
+        };
+    }
+
+    Outcome::Matched(fields)
+}
+
+/// Check the values of a structurally matching input, returning the first invalid field along
+/// with the reason and a possible correction.
+fn check_fields<'a>(
+    fields: &'a [Field],
+    input: &str,
+) -> Option<(&'a Field, String, Option<String>)> {
+    let value = |spec: char| {
+        fields
+            .iter()
+            .find(|field| field.spec == spec)
+            .and_then(|field| input[field.start..field.end].parse::<u32>().ok())
+    };
+
+    for field in fields {
+        let text = &input[field.start..field.end];
+        let number = text.parse::<u32>().unwrap_or(0);
+
+        let (reason, suggestion) = match field.spec {
+            'B' | 'b' | 'h' if parse_month_name(text).is_none() => (
+                format!("unknown month name `{}`", text),
+                closest_word(text, &MONTHS)
+                    .or_else(|| {
+                        let abbreviations: Vec<&str> =
+                            MONTHS.iter().map(|month| &month[..3]).collect();
+                        closest_word(text, &abbreviations)
+                    })
+                    .map(|month| replace(input, field, month)),
+            ),
+            'm' if !(1..=12).contains(&number) => (
+                format!("month {} is out of range (1-12)", number),
+                // Most likely the day and month were just the wrong way around.
+                match fields.iter().find(|day| day.spec == 'd') {
+                    Some(day) if (1..=12).contains(&value('d').unwrap_or(0)) && number <= 31 => {
+                        Some(swap(input, field, day))
+                    }
+                    _ => None,
+                },
+            ),
+            'd' if !(1..=31).contains(&number) => {
+                (format!("day {} is out of range (1-31)", number), None)
+            }
+            'd' => match (value('Y'), value('m').filter(|month| *month <= 12)) {
+                (Some(year), Some(month)) if number > days_in_month(year as i32, month) => (
+                    format!(
+                        "{} {} only has {} days",
+                        MONTHS[month as usize - 1],
+                        year,
+                        days_in_month(year as i32, month)
+                    ),
+                    None,
+                ),
+                _ => continue,
+            },
+            'H' if number > 23 => (format!("hour {} is out of range (0-23)", number), None),
+            'M' if number > 59 => (format!("minute {} is out of range (0-59)", number), None),
+            // 60 is a leap second
+            'S' if number > 60 => (format!("second {} is out of range (0-60)", number), None),
+            'V' if !(1..=53).contains(&number) => {
+                (format!("week {} is out of range (1-53)", number), None)
+            }
+            'u' if !(1..=7).contains(&number) => {
+                (format!("weekday {} is out of range (1-7)", number), None)
+            }
+            'j' if !(1..=366).contains(&number) => (
+                format!("day of year {} is out of range (1-366)", number),
+                None,
+            ),
+            _ => continue,
+        };
+
+        return Some((field, reason, suggestion));
+    }
+
+    None
+}
+
+/// Whether the input is a valid datetime, date or time in the format, regardless of the timezone.
+fn is_valid_local(input: &str, format: &str) -> bool {
+    NaiveDateTime::parse_from_str(input, format).is_ok()
+        || NaiveDate::parse_from_str(input, format).is_ok()
+        || NaiveTime::parse_from_str(input, format).is_ok()
+}
+
+/// Look for typos in relative expressions, eg. `tomorow`.
+fn suggest_keyword(input: &str) -> Option<ParseError> {
+    let mut position = 0;
+
+    for word in input.split(' ') {
+        let start = position;
+        position += word.len() + 1;
+
+        let lowercase = word.to_lowercase();
+        if word.is_empty() || !word.chars().all(char::is_alphabetic) {
+            continue;
+        }
+        if KEYWORDS.contains(&lowercase.as_str()) || KEYWORDS.contains(&&*format!("{}s", lowercase))
+        {
+            continue;
+        }
+
+        let keyword = closest_word(&lowercase, &KEYWORDS)?;
+        let field = Field {
+            spec: 'w',
+            start,
+            end: start + word.len(),
+        };
+
+        return Some(ParseError {
+            input: input.to_string(),
+            closest_format: None,
+            position: start,
+            length: word.len(),
+            reason: format!("unknown word `{}`", word),
+            suggestion: Some(replace(input, &field, keyword)),
+        });
+    }
+
+    None
+}
+
+/// Find the candidate within a couple of typos from the word, ignoring case. Short words need to
+/// be closer, otherwise anything would be a typo of `now`.
+pub(crate) fn closest_word<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let word = word.to_lowercase();
+
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein(&word, &candidate.to_lowercase()), *candidate))
+        .filter(|(distance, _)| *distance <= 2 && *distance * 3 <= word.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn parse_month_name(name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+
+    MONTHS.iter().position(|month| {
+        let month = month.to_lowercase();
+        name == month || name == month[..3]
+    })
+}
+
+fn replace(input: &str, field: &Field, value: &str) -> String {
+    format!("{}{}{}", &input[..field.start], value, &input[field.end..])
+}
+
+fn swap(input: &str, first: &Field, second: &Field) -> String {
+    let (first, second) = if first.start < second.start {
+        (first, second)
+    } else {
+        (second, first)
+    };
+
+    format!(
+        "{}{}{}{}{}",
+        &input[..first.start],
+        &input[second.start..second.end],
+        &input[first.end..second.start],
+        &input[first.start..first.end],
+        &input[second.end..]
+    )
+}
+
+fn count_digits(input: &str, max: usize) -> usize {
+    input
+        .chars()
+        .take(max)
+        .take_while(|c| c.is_ascii_digit())
+        .count()
+}
+
+/// Human-readable name for a single format specifier.
+fn describe_spec(spec: char) -> &'static str {
+    match spec {
+        'Y' | 'G' => "a year",
+        'm' => "a month",
+        'B' | 'b' | 'h' => "a month name",
+        'A' | 'a' => "a weekday name",
+        'p' | 'P' => "AM or PM",
+        'z' => "an offset",
+        '%' => "`%`",
+        'd' => "a day",
+        'H' => "an hour",
+        'M' => "minutes",
+        'S' => "seconds",
+        'V' => "a week number",
+        'u' => "a weekday number",
+        'j' => "a day of year",
+        _ => "a number",
+    }
+}
+
+/// Turn a strftime format into something readable, eg. `%d.%m.%Y` -> `DD.MM.YYYY`.
+fn describe_format(format: &str) -> String {
+    format
+        .replace("%T", "HH:MM:SS")
+        .replace("%R", "HH:MM")
+        .replace("%.f", "[.fff]")
+        .replace("%Y", "YYYY")
+        .replace("%G", "YYYY")
+        .replace("%m", "MM")
+        .replace("%d", "DD")
+        .replace("%B", "MONTH")
+        .replace("%b", "MON")
+        .replace("%H", "HH")
+        .replace("%M", "MM")
+        .replace("%S", "SS")
+        .replace("%V", "ww")
+        .replace("%u", "D")
+        .replace("%j", "DDD")
+}
@@ -0,0 +1,74 @@
+use std::{error, fmt, io};
+
+/// Error for arguments that couldn't be parsed into a datetime with any of the supported formats.
+///
+/// Describes how close the argument got to being parsed: which format matched most of it, where
+/// it went wrong and, if possible, what was probably meant instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub(crate) input: String,
+    pub(crate) closest_format: Option<String>,
+    pub(crate) position: usize,
+    pub(crate) length: usize,
+    pub(crate) reason: String,
+    pub(crate) suggestion: Option<String>,
+}
+
+impl ParseError {
+    /// The argument that failed to parse.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Human-readable version of the format that matched the longest part of the input, eg.
+    /// `DD.MM.YYYY`. `None` if nothing matched at all.
+    pub fn closest_format(&self) -> Option<&str> {
+        self.closest_format.as_deref()
+    }
+
+    /// Byte range of the input where parsing went wrong.
+    pub fn span(&self) -> (usize, usize) {
+        (self.position, self.position + self.length)
+    }
+
+    /// Explanation of what went wrong, eg. "month 13 is out of range (1-12)".
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Corrected version of the input, if there's a likely candidate.
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.closest_format {
+            Some(format) => write!(f, "{} (closest format: {})", self.reason, format),
+            None => self.reason.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Error for a config file that couldn't be read.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file exists but couldn't be read.
+    Io(io::Error),
+    /// The file isn't valid, or has unknown settings.
+    Syntax { line: usize, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => err.fmt(f),
+            ConfigError::Syntax { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
@@ -1,18 +1,71 @@
-use crate::calculators::{calculate_month_diff, calculate_year_diff};
+use crate::calculators::{
+    calculate_breakdown, calculate_fractional_month_diff, calculate_fractional_year_diff,
+    calculate_month_diff, calculate_units, calculate_work_duration, calculate_year_diff,
+    duration_as_nanoseconds, duration_as_seconds, Breakdown, Difference, WorkCalendar,
+};
+use crate::errors::ParseError;
 use crate::subcommands::Filter;
-use chrono::{DateTime, Duration, Local, TimeZone};
+use crate::zones::Zone;
+use chrono::{DateTime, Duration, TimeZone};
+
+/// Output modifiers shared by all subcommands.
+#[derive(Default)]
+pub struct OutputOptions {
+    /// Print fractional values with this many decimals, instead of rounding down.
+    pub decimals: Option<usize>,
+    /// Keep the direction of the difference: negative values and "in X"/"X ago" for shorthands.
+    pub signed: bool,
+    /// Phrase shorthands as a countdown towards `to`, eg. "3 days left".
+    pub countdown: bool,
+    /// Working days and hours for `workdays` and `workhours`.
+    pub calendar: WorkCalendar,
+}
 
 /// Return the requested time difference filtered according to the chosen subcommand.
 ///
 /// If no subcommand is chosen, guess which is the best format for humans to read
 /// for the given time range.
-pub fn get_output(from: DateTime<Local>, to: DateTime<Local>, filter: Filter) -> String {
+pub fn get_output(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    filter: Filter,
+    options: &OutputOptions,
+) -> String {
+    // NOTE:
+    // By default all values are printed in absolutes, as to not show negative number for values
+    // in future. While this is breaking the semantics of `since` a bit, we'll allow it for
+    // better usability. Only the wording of the shorthands changes for `until`.
+    let output = match options.decimals {
+        Some(decimals) => get_decimal_output(from, to, filter, decimals, &options.calendar),
+        None => get_absolute_output(from, to, filter, &options.calendar),
+    };
+
+    let is_negative = from > to;
+    match filter {
+        Filter::None | Filter::Full if options.countdown && is_negative => {
+            format!("{} ago", output)
+        }
+        Filter::None | Filter::Full if options.countdown => format!("{} left", output),
+        _ if !options.signed => output,
+        Filter::None | Filter::Full if is_negative => format!("in {}", output),
+        Filter::None | Filter::Full => format!("{} ago", output),
+        // Don't bother with a sign if everything was rounded down to zero anyway.
+        _ if is_negative && output.contains(|c| ('1'..='9').contains(&c)) => {
+            format!("-{}", output)
+        }
+        _ => output,
+    }
+}
+
+/// Return the absolute time difference in whole units.
+fn get_absolute_output(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    filter: Filter,
+    calendar: &WorkCalendar,
+) -> String {
     let difference = to.signed_duration_since(from);
 
-    // NOTE:
-    // All values are printed in absolutes, as to not show negative number for values in
-    // future. While this is breaking the semantics of `since` a bit, we'll allow it for
-    // better usability. You could basically just symlink `since` -> `until`.
     match filter {
         Filter::Years => calculate_year_diff(from, to).to_string(),
         Filter::Months => calculate_month_diff(from, to).to_string(),
@@ -21,18 +74,67 @@ pub fn get_output(from: DateTime<Local>, to: DateTime<Local>, filter: Filter) ->
         Filter::Hours => difference.num_hours().abs().to_string(),
         Filter::Minutes => difference.num_minutes().abs().to_string(),
         Filter::Seconds => difference.num_seconds().abs().to_string(),
+        Filter::Milliseconds | Filter::Microseconds | Filter::Nanoseconds => {
+            let nanos = duration_as_nanoseconds(difference).abs();
+            (nanos / nanoseconds_per_unit(filter)).to_string()
+        }
+        Filter::Full => get_full_output(from, to, None),
+        Filter::Clock => get_clock_output(difference, None),
+        Filter::Workdays | Filter::Workhours => get_work_output(from, to, filter, calendar, None),
         Filter::None => get_shorthand_output(from, to, difference),
     }
 }
 
+/// Same as `get_output`, but with fractional values instead of whole units.
+fn get_decimal_output(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    filter: Filter,
+    decimals: usize,
+    calendar: &WorkCalendar,
+) -> String {
+    let seconds = duration_as_seconds(to.signed_duration_since(from)).abs();
+
+    let output = match filter {
+        Filter::Years => calculate_fractional_year_diff(from, to),
+        Filter::Months => calculate_fractional_month_diff(from, to),
+        Filter::Weeks => seconds / 60.0 / 60.0 / 24.0 / 7.0,
+        Filter::Days => seconds / 60.0 / 60.0 / 24.0,
+        Filter::Hours => seconds / 60.0 / 60.0,
+        Filter::Minutes => seconds / 60.0,
+        Filter::Seconds => seconds,
+        Filter::Milliseconds => seconds * 1e3,
+        Filter::Microseconds => seconds * 1e6,
+        Filter::Nanoseconds => seconds * 1e9,
+        Filter::Full => return get_full_output(from, to, Some(decimals)),
+        Filter::Clock => {
+            return get_clock_output(to.signed_duration_since(from), Some(decimals));
+        }
+        Filter::Workdays | Filter::Workhours => {
+            return get_work_output(from, to, filter, calendar, Some(decimals));
+        }
+        // Same guesses as in the shorthand output, but only a single unit is needed here.
+        Filter::None => {
+            let (value, unit) = match seconds as i64 / 60 / 60 / 24 {
+                63..=730 => (calculate_fractional_month_diff(from, to), "months"),
+                2..=62 => (seconds / 60.0 / 60.0 / 24.0, "days"),
+                0..=1 if seconds < 1.0 => (seconds * 1e3, "milliseconds"),
+                0..=1 if seconds < 60.0 => (seconds, "seconds"),
+                0..=1 if seconds < 60.0 * 60.0 => (seconds / 60.0, "minutes"),
+                0..=1 => (seconds / 60.0 / 60.0, "hours"),
+                _ => (calculate_fractional_year_diff(from, to), "years"),
+            };
+            return format!("{:.*} {}", decimals, value, unit);
+        }
+    };
+
+    format!("{:.*}", decimals, output)
+}
+
 /// Print the time difference for shorthand use.
 ///
 /// Does some basic guessing on which format is the nicest for user to read.
-fn get_shorthand_output(
-    from: DateTime<Local>,
-    to: DateTime<Local>,
-    difference: Duration,
-) -> String {
+fn get_shorthand_output(from: DateTime<Zone>, to: DateTime<Zone>, difference: Duration) -> String {
     // TODO: Clean this up and add "and"-clauses to every option, like in hours/minutes now.
     let days = difference.num_days().abs();
     match days {
@@ -43,24 +145,318 @@ fn get_shorthand_output(
         0..=1 => {
             let hours = difference.num_hours().abs();
             let mins = (difference.num_minutes() % 60).abs();
+            let secs = (difference.num_seconds() % 60).abs();
+            let millis = difference.num_milliseconds().abs();
 
-            // TODO: Seconds
-            if hours == 0 {
-                format!("{} minutes", mins)
+            if hours == 0 && mins == 0 && secs == 0 && millis > 0 {
+                pluralize(millis, "millisecond")
+            } else if hours == 0 && mins == 0 {
+                pluralize(secs, "second")
+            } else if hours == 0 && secs == 0 {
+                pluralize(mins, "minute")
+            } else if hours == 0 {
+                format!(
+                    "{} and {}",
+                    pluralize(mins, "minute"),
+                    pluralize(secs, "second")
+                )
+            } else if mins == 0 {
+                pluralize(hours, "hour")
             } else {
-                format!("{} hours and {} minutes", hours, mins)
+                format!(
+                    "{} and {}",
+                    pluralize(hours, "hour"),
+                    pluralize(mins, "minute")
+                )
             }
         }
         _ => format!("{} years", calculate_year_diff(from, to)),
     }
 }
 
+/// Length of a sub-second unit in nanoseconds.
+fn nanoseconds_per_unit(filter: Filter) -> i128 {
+    match filter {
+        Filter::Milliseconds => 1_000_000,
+        Filter::Microseconds => 1_000,
+        _ => 1,
+    }
+}
+
+/// Print the absolute working time between the datetimes, in either working days or hours.
+///
+/// A working day is as long as the working hours, so eg. 8 working hours make up a full day on a
+/// 09:00-17:00 schedule, regardless of which days they were on.
+fn get_work_output(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    filter: Filter,
+    calendar: &WorkCalendar,
+    decimals: Option<usize>,
+) -> String {
+    let work = calculate_work_duration(from, to, calendar);
+    let unit = match filter {
+        Filter::Workdays => calendar.day_length(),
+        _ => Duration::hours(1),
+    };
+
+    match decimals {
+        Some(decimals) => format!(
+            "{:.*}",
+            decimals,
+            duration_as_seconds(work) / duration_as_seconds(unit)
+        ),
+        None => work
+            .num_seconds()
+            .checked_div(unit.num_seconds())
+            .unwrap_or(0)
+            .to_string(),
+    }
+}
+
+/// Print every non-zero unit of the time difference, from years all the way down to seconds.
+///
+/// Eg. "1 year, 2 months, 3 days, 5 hours and 15 minutes".
+fn get_full_output(from: DateTime<Zone>, to: DateTime<Zone>, decimals: Option<usize>) -> String {
+    let Breakdown {
+        years,
+        months,
+        days,
+        hours,
+        minutes,
+        seconds,
+    } = calculate_breakdown(from, to);
+
+    let mut parts: Vec<String> = [
+        (years, "year"),
+        (months, "month"),
+        (days, "day"),
+        (hours, "hour"),
+        (minutes, "minute"),
+    ]
+    .iter()
+    .filter(|(value, _)| *value != 0)
+    .map(|(value, unit)| pluralize(*value, unit))
+    .collect();
+
+    // Seconds are the only unit that can have a fraction left over.
+    let fraction = match decimals {
+        Some(_) => duration_as_seconds(to.signed_duration_since(from))
+            .abs()
+            .fract(),
+        None => 0.0,
+    };
+
+    if seconds != 0 || fraction != 0.0 || parts.is_empty() {
+        parts.push(match decimals {
+            Some(decimals) => format!("{:.*} seconds", decimals, seconds as f64 + fraction),
+            None => pluralize(seconds, "second"),
+        });
+    }
+
+    let last = parts.pop().unwrap_or_default();
+    if parts.is_empty() {
+        last
+    } else {
+        format!("{} and {}", parts.join(", "), last)
+    }
+}
+
+/// Print the absolute time difference in clock notation, `[D-]HH:MM:SS`, eg. "03:12:07" or
+/// "2-03:12:07" past a day.
+///
+/// With decimals the seconds get a fraction as well, eg. "03:12:07.250". Unlike everywhere else,
+/// the fraction is always rounded down so that a clock never shows a second too early.
+fn get_clock_output(difference: Duration, decimals: Option<usize>) -> String {
+    let difference = if difference < Duration::zero() {
+        -difference
+    } else {
+        difference
+    };
+
+    let seconds = difference.num_seconds();
+    let clock = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 60 / 60 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    let clock = match seconds / 60 / 60 / 24 {
+        0 => clock,
+        days => format!("{}-{}", days, clock),
+    };
+
+    match decimals.map(|decimals| decimals.min(9)) {
+        None | Some(0) => clock,
+        Some(decimals) => {
+            let nanos = (difference - Duration::seconds(seconds))
+                .num_nanoseconds()
+                .unwrap_or_default();
+            let fraction = nanos / 10_i64.pow(9 - decimals as u32);
+            format!("{}.{:0width$}", clock, fraction, width = decimals)
+        }
+    }
+}
+
+fn pluralize(value: i64, unit: &str) -> String {
+    if value == 1 {
+        format!("{} {}", value, unit)
+    } else {
+        format!("{} {}s", value, unit)
+    }
+}
+
+/// Single piece of an output template, see `parse_template`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    /// Value of a single unit, optionally zero-padded to two digits.
+    Field {
+        unit: Filter,
+        padded: bool,
+    },
+}
+
+/// Parse an output template, eg. `{d}d {h}h {m}m` or `%H:%M:%S`.
+///
+/// Fields in braces are `{y}`, `{mo}`, `{w}`, `{d}`, `{h}`, `{m}` and `{s}`, zero-padded with a
+/// leading zero like `{0h}`. The strftime-like fields `%Y`, `%m`, `%W`, `%d`, `%H`, `%M` and `%S`
+/// are zero-padded by default, and unpadded like `%-H`. `{{`, `}}` and `%%` are literals.
+pub fn parse_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let field = match c {
+            '{' if chars.peek() == Some(&'{') => None,
+            '}' if chars.peek() == Some(&'}') => None,
+            '%' if chars.peek() == Some(&'%') => None,
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err(String::from("unclosed `{`, use `{{` for a literal one"))
+                        }
+                    }
+                }
+                let (padded, unit) = match name.strip_prefix('0') {
+                    Some(unit) => (true, unit),
+                    None => (false, name.as_str()),
+                };
+
+                let unit = match unit {
+                    "y" => Filter::Years,
+                    "mo" => Filter::Months,
+                    "w" => Filter::Weeks,
+                    "d" => Filter::Days,
+                    "h" => Filter::Hours,
+                    "m" => Filter::Minutes,
+                    "s" => Filter::Seconds,
+                    _ => return Err(format!("unknown field `{{{}}}`", name)),
+                };
+                Some(TemplatePart::Field { unit, padded })
+            }
+            '%' => {
+                let padded = chars.next_if_eq(&'-').is_none();
+                let unit = match chars.next() {
+                    Some('Y') => Filter::Years,
+                    Some('m') => Filter::Months,
+                    Some('W') => Filter::Weeks,
+                    Some('d') => Filter::Days,
+                    Some('H') => Filter::Hours,
+                    Some('M') => Filter::Minutes,
+                    Some('S') => Filter::Seconds,
+                    Some(spec) => return Err(format!("unknown field `%{}`", spec)),
+                    None => return Err(String::from("unfinished field `%` at the end")),
+                };
+                Some(TemplatePart::Field { unit, padded })
+            }
+            '}' => return Err(String::from("unmatched `}`, use `}}` for a literal one")),
+            _ => {
+                text.push(c);
+                continue;
+            }
+        };
+
+        match field {
+            Some(field) => {
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(text.split_off(0)));
+                }
+                parts.push(field);
+            }
+            // Escaped literal
+            None => text.push(chars.next().unwrap_or(c)),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+
+    Ok(parts)
+}
+
+/// Fill out the parsed template with the time difference. The largest unit in the template holds
+/// everything above it, eg. `%H:%M` is `27:00` for a difference of 1 day and 3 hours.
+pub fn get_template_output(
+    from: DateTime<Zone>,
+    to: DateTime<Zone>,
+    template: &[TemplatePart],
+    options: &OutputOptions,
+) -> String {
+    let units: Vec<Filter> = template
+        .iter()
+        .filter_map(|part| match part {
+            TemplatePart::Field { unit, .. } => Some(*unit),
+            TemplatePart::Text(_) => None,
+        })
+        .collect();
+    let mut values = calculate_units(from, to, &units).into_iter();
+
+    let output: String = template
+        .iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => text.clone(),
+            TemplatePart::Field { padded: true, .. } => {
+                format!("{:02}", values.next().unwrap_or_default())
+            }
+            TemplatePart::Field { padded: false, .. } => {
+                values.next().unwrap_or_default().to_string()
+            }
+        })
+        .collect();
+
+    if options.signed && from > to {
+        format!("-{}", output)
+    } else {
+        output
+    }
+}
+
 /// Return the UNIX timestamp filtered according to the chosen subcommand.
-pub fn get_epoch_output(now: DateTime<Local>, filter: Filter) -> String {
-    let epoch = Local::now().timestamp();
-    let epoch_date = Local.ymd(1970, 1, 1).and_hms(0, 0, 0);
+pub fn get_epoch_output(now: DateTime<Zone>, filter: Filter, options: &OutputOptions) -> String {
+    let epoch = now.timestamp();
+    let epoch_date = now.timezone().ymd(1970, 1, 1).and_hms(0, 0, 0);
+
+    if let Some(decimals) = options.decimals {
+        return get_decimal_epoch_output(now, epoch_date, filter, decimals, &options.calendar);
+    }
 
     let output: i64 = match filter {
+        Filter::Milliseconds | Filter::Microseconds | Filter::Nanoseconds => {
+            let nanos = epoch as i128 * 1_000_000_000 + now.timestamp_subsec_nanos() as i128;
+            return nanos.div_euclid(nanoseconds_per_unit(filter)).to_string();
+        }
+        Filter::Full => return get_full_output(epoch_date, now, None),
+        Filter::Clock => return get_clock_output(now.signed_duration_since(epoch_date), None),
+        Filter::Workdays | Filter::Workhours => {
+            return get_work_output(epoch_date, now, filter, &options.calendar, None);
+        }
         // Epoch days are always statically 86400 seconds long.
         // Thus the following calculations are just "close enough" approximations
         Filter::Years => calculate_year_diff(epoch_date, now),
@@ -76,3 +472,159 @@ pub fn get_epoch_output(now: DateTime<Local>, filter: Filter) -> String {
 
     output.to_string()
 }
+
+/// Same as `get_epoch_output`, but with fractional values instead of whole units.
+fn get_decimal_epoch_output(
+    now: DateTime<Zone>,
+    epoch_date: DateTime<Zone>,
+    filter: Filter,
+    decimals: usize,
+    calendar: &WorkCalendar,
+) -> String {
+    let epoch = now.timestamp() as f64 + now.timestamp_subsec_nanos() as f64 / 1e9;
+
+    let output = match filter {
+        Filter::Full => return get_full_output(epoch_date, now, Some(decimals)),
+        Filter::Clock => {
+            return get_clock_output(now.signed_duration_since(epoch_date), Some(decimals));
+        }
+        Filter::Workdays | Filter::Workhours => {
+            return get_work_output(epoch_date, now, filter, calendar, Some(decimals));
+        }
+        Filter::Years => calculate_fractional_year_diff(epoch_date, now),
+        Filter::Months => calculate_fractional_month_diff(epoch_date, now),
+        Filter::Weeks => epoch / 60.0 / 60.0 / 24.0 / 7.0,
+        Filter::Days => epoch / 60.0 / 60.0 / 24.0,
+        Filter::Hours => epoch / 60.0 / 60.0,
+        Filter::Minutes => epoch / 60.0,
+        Filter::Seconds => epoch,
+        Filter::Milliseconds => epoch * 1e3,
+        Filter::Microseconds => epoch * 1e6,
+        Filter::Nanoseconds => epoch * 1e9,
+        Filter::None => epoch,
+    };
+
+    format!("{:.*}", decimals, output)
+}
+
+/// Return the calendar breakdown of the time difference as an ISO 8601 duration, eg.
+/// "P1Y2M3DT4H5M6S". With decimals the seconds get a fraction, eg. "PT7.25S".
+pub fn get_iso_output(from: DateTime<Zone>, to: DateTime<Zone>, options: &OutputOptions) -> String {
+    let fraction = duration_as_seconds(to.signed_duration_since(from))
+        .abs()
+        .fract();
+    let duration = format_iso_duration(
+        calculate_breakdown(from, to),
+        options.decimals.map(|decimals| (fraction, decimals)),
+    );
+
+    if options.signed && from > to {
+        format!("-{}", duration)
+    } else {
+        duration
+    }
+}
+
+/// Zero units are left out, except for an empty duration which is "PT0S".
+fn format_iso_duration(breakdown: Breakdown, fraction: Option<(f64, usize)>) -> String {
+    let Breakdown {
+        years,
+        months,
+        days,
+        hours,
+        minutes,
+        seconds,
+    } = breakdown;
+
+    let unit = |value: i64, designator: char| match value {
+        0 => String::new(),
+        value => format!("{}{}", value, designator),
+    };
+    let seconds = match fraction {
+        Some((fraction, decimals)) if fraction != 0.0 => {
+            format!("{:.*}S", decimals, seconds as f64 + fraction)
+        }
+        _ => unit(seconds, 'S'),
+    };
+
+    let date = [unit(years, 'Y'), unit(months, 'M'), unit(days, 'D')].concat();
+    let time = [unit(hours, 'H'), unit(minutes, 'M'), seconds].concat();
+
+    match (date.as_str(), time.as_str()) {
+        ("", "") => String::from("PT0S"),
+        (date, "") => format!("P{}", date),
+        (date, time) => format!("P{}T{}", date, time),
+    }
+}
+
+/// Return every unit of the time difference as a single JSON object, for use in other programs.
+///
+/// Contains both datetimes in RFC 3339, the signed difference in each unit, the absolute calendar
+/// breakdown, the signed ISO 8601 duration and the shorthand.
+pub fn get_json_output(difference: &Difference) -> String {
+    let Breakdown {
+        years,
+        months,
+        days,
+        hours,
+        minutes,
+        seconds,
+    } = difference.breakdown();
+
+    format!(
+        concat!(
+            r#"{{"from":{},"to":{},"negative":{},"#,
+            r#""difference":{{"years":{},"months":{},"weeks":{},"days":{},"hours":{},"minutes":{},"seconds":{},"#,
+            r#""milliseconds":{},"microseconds":{},"nanoseconds":{}}},"#,
+            r#""breakdown":{{"years":{},"months":{},"days":{},"hours":{},"minutes":{},"seconds":{}}},"#,
+            r#""iso8601":{},"shorthand":{}}}"#,
+        ),
+        json_string(&difference.from().to_rfc3339()),
+        json_string(&difference.to().to_rfc3339()),
+        difference.is_negative(),
+        difference.years(),
+        difference.months(),
+        difference.weeks(),
+        difference.days(),
+        difference.hours(),
+        difference.minutes(),
+        difference.seconds(),
+        difference.milliseconds(),
+        difference.microseconds(),
+        difference.nanoseconds(),
+        years,
+        months,
+        days,
+        hours,
+        minutes,
+        seconds,
+        json_string(&difference.iso8601()),
+        json_string(&difference.shorthand()),
+    )
+}
+
+/// Return a JSON object for an input that couldn't be parsed, in place of the usual output.
+pub fn get_json_error_output(error: &ParseError) -> String {
+    format!(
+        r#"{{"input":{},"error":{}}}"#,
+        json_string(error.input()),
+        json_string(&error.to_string())
+    )
+}
+
+/// Quote and escape the value as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+    output
+}
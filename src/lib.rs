@@ -0,0 +1,36 @@
+//! Time differences with semantic output for humans.
+//!
+//! The same parsing rules and calculations as the `since` command line utility, for use in other
+//! programs:
+//!
+//! ```
+//! use since::{try_parse_all_formats, Difference, Zone};
+//!
+//! let now = Zone::Local.now();
+//! let from = try_parse_all_formats("3 days ago", now).unwrap();
+//! let difference = Difference::new(from, now);
+//!
+//! assert_eq!(difference.days(), 3);
+//! assert_eq!(difference.shorthand(), "3 days");
+//! ```
+
+pub mod bookmarks;
+pub mod calculators;
+pub mod config;
+mod diagnostics;
+pub mod errors;
+pub mod formatters;
+pub mod parsers;
+mod storage;
+pub mod subcommands;
+pub mod timers;
+pub mod zones;
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::calculators::{Breakdown, Difference};
+pub use crate::errors::ParseError;
+pub use crate::parsers::{try_parse_all_formats, try_parse_with_options, DateOrder, ParseOptions};
+pub use crate::subcommands::Filter;
+pub use crate::zones::Zone;
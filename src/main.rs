@@ -1,59 +1,1199 @@
-mod calculators;
-mod formatters;
-mod parsers;
-mod subcommands;
-
-#[cfg(test)]
-mod tests;
-
-use crate::formatters::{get_epoch_output, get_output};
-use crate::parsers::try_parse_all_formats;
-use crate::subcommands::Filter;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, TimeZone};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
-use std::process;
+use since::bookmarks::{bookmarks_path, load_bookmarks, save_bookmarks, Bookmarks};
+use since::calculators::{add_breakdown, until_next_tick, WorkCalendar};
+use since::config::{config_path, load_config, load_holidays, Config};
+use since::formatters::{
+    get_epoch_output, get_iso_output, get_json_error_output, get_json_output, get_output,
+    get_template_output, parse_template, OutputOptions,
+};
+use since::parsers::{
+    check_date_order, is_date_input, try_parse_duration, try_parse_intervals, try_parse_range,
+    try_parse_work_hours,
+};
+use since::subcommands::{
+    ABBREVIATIONS, ADD, LAP, MARK, MARKS, PAUSE, RESUME, START, STOP, SUB, UNTIL,
+};
+use since::timers::{load_timers, save_timers, timers_path, Timer, Timers};
+use since::zones::parse_zone;
+use since::{
+    try_parse_all_formats, try_parse_with_options, DateOrder, Difference, Filter, ParseError,
+    ParseOptions, Zone,
+};
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::{env, process, thread};
 
-fn handle_args(filter: Filter, matches: &ArgMatches) {
-    let now = Local::now();
+fn handle_args(filter: Filter, matches: &ArgMatches, countdown: bool) {
+    // Validated by Clap already
+    let zone = matches
+        .value_of("tz")
+        .and_then(parse_zone)
+        .unwrap_or(Zone::Local);
+    let now = zone.now();
+    let parse_options = build_parse_options(matches);
 
-    let from: DateTime<Local> = match matches.value_of("from") {
-        Some(arg) => match try_parse_all_formats(arg, now) {
+    let options = OutputOptions {
+        decimals: if matches.is_present("decimal") {
+            // Validated by Clap already
+            matches
+                .value_of("precision")
+                .and_then(|val| val.parse().ok())
+        } else {
+            None
+        },
+        signed: matches.is_present("signed"),
+        countdown,
+        calendar: build_work_calendar(matches, now, &parse_options),
+    };
+    // Validated by Clap already
+    let format = matches.value_of("format").unwrap_or("text");
+    // Validated by Clap already
+    let template = matches
+        .value_of("output-format")
+        .and_then(|template| parse_template(template).ok());
+
+    let render = |from: DateTime<Zone>, to: DateTime<Zone>| match format {
+        "json" => get_json_output(&Difference::new(from, to)),
+        "iso" => get_iso_output(from, to, &options),
+        _ => match &template {
+            Some(template) => get_template_output(from, to, template, &options),
+            None => get_output(from, to, filter, &options),
+        },
+    };
+
+    if matches.is_present("stdin") {
+        handle_stdin(now, countdown, &parse_options, format == "json", render);
+    }
+
+    if let Some(path) = matches.value_of("timesheet") {
+        handle_timesheet(path, now, &parse_options, format == "json", render);
+    }
+
+    let parse = |arg: &str| {
+        if let Some(warning) = check_ambiguity(arg, &parse_options) {
+            eprintln!("Warning: {}", warning);
+        }
+        try_parse_with_options(arg, now, &parse_options)
+    };
+
+    let from: DateTime<Zone> = match matches.value_of("from") {
+        Some(arg) => match parse(arg) {
             Ok(datetime) => datetime,
             Err(err) => {
                 eprintln!("Unable to parse FROM arg `{}` into datetime: {}.", arg, err);
+                print_parse_error(&err);
                 process::exit(1);
             }
         },
+        // Countdowns are always relative to the current moment
+        None if countdown => now,
+        // Other formats and templates always describe a full range, so just start it from the
+        // epoch.
+        None if format != "text" || template.is_some() => zone.timestamp(0, 0),
         None => {
-            println!("{}", get_epoch_output(now, filter));
+            println!("{}", get_epoch_output(now, filter, &options));
             process::exit(0);
         }
     };
 
-    let to: DateTime<Local> = match matches.value_of("to") {
-        Some(arg) => match try_parse_all_formats(arg, now) {
+    let to: DateTime<Zone> = match matches.value_of("to") {
+        Some(arg) => match parse(arg) {
             Ok(datetime) => datetime,
             Err(err) => {
                 eprintln!("Unable to parse TO arg `{}` into datetime: {}.", arg, err);
+                print_parse_error(&err);
                 process::exit(1);
             }
         },
         None => now,
     };
 
-    println!("{}", get_output(from, to, filter));
+    if countdown && from > to {
+        eprintln!(
+            "Warning: target `{}` is already in the past.",
+            matches.value_of("to").unwrap_or_default()
+        );
+    }
+
+    // Only the current datetime moves, so there's nothing to watch if both ends were given.
+    let is_moving = if countdown {
+        matches.value_of("from").is_none()
+    } else {
+        matches.value_of("to").is_none()
+    };
+
+    if matches.is_present("watch") && is_moving {
+        // Validated by Clap already
+        let interval = matches
+            .value_of("interval")
+            .and_then(|val| val.parse::<f64>().ok())
+            .map_or(Duration::seconds(1), |seconds| {
+                Duration::nanoseconds((seconds * 1e9) as i64)
+            });
+        let command = matches.value_of("exec");
+
+        if countdown {
+            handle_watch(to, interval, command, |now| render(now, to));
+        } else {
+            handle_watch(from, interval, command, |now| render(from, now));
+        }
+    }
+
+    println!("{}", render(from, to));
+
+    // Let scripts branch on the direction without doing any date math themselves.
+    if (options.signed || countdown) && from > to {
+        process::exit(2);
+    }
 }
 
-fn main() {
-    let from: Arg = Arg::with_name("from")
-        .help("Start time or date.")
-        .required(false)
-        .index(1);
+/// Move the datetime by the duration, backwards when subtracting, and print the result.
+fn handle_shift(matches: &ArgMatches, subtract: bool) {
+    // Validated by Clap already
+    let zone = matches
+        .value_of("tz")
+        .and_then(parse_zone)
+        .unwrap_or(Zone::Local);
+    let now = zone.now();
+    let parse_options = build_parse_options(matches);
+
+    // Both are required, so Clap makes sure they exist.
+    let arg = matches.value_of("datetime").unwrap_or_default();
+    let duration_arg = matches.value_of("duration").unwrap_or_default();
+
+    if let Some(warning) = check_ambiguity(arg, &parse_options) {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let datetime = match try_parse_with_options(arg, now, &parse_options) {
+        Ok(datetime) => datetime,
+        Err(err) => {
+            eprintln!(
+                "Unable to parse DATETIME arg `{}` into datetime: {}.",
+                arg, err
+            );
+            print_parse_error(&err);
+            process::exit(1);
+        }
+    };
+
+    let duration = match try_parse_duration(duration_arg) {
+        Ok(duration) if subtract => -duration,
+        Ok(duration) => duration,
+        Err(err) => {
+            eprintln!(
+                "Unable to parse DURATION arg `{}` into duration: {}.",
+                duration_arg, err
+            );
+            print_parse_error(&err);
+            process::exit(1);
+        }
+    };
+
+    let result = match add_breakdown(datetime, &duration) {
+        Some(result) => result,
+        None => {
+            eprintln!(
+                "Unable to {} `{}`: the result is out of range or doesn't exist in the timezone.",
+                if subtract { SUB } else { ADD },
+                duration_arg
+            );
+            process::exit(1);
+        }
+    };
+
+    // Dates get the current time filled in, which is just noise if the duration is in full days.
+    let is_date = duration.hours == 0
+        && duration.minutes == 0
+        && duration.seconds == 0
+        && is_date_input(arg, &parse_options);
+
+    if is_date {
+        println!("{}", result.format("%Y-%m-%d"));
+    } else {
+        println!("{}", result.format("%Y-%m-%d %H:%M:%S"));
+    }
+}
+
+/// Redraw the difference between the current datetime and `target` on every tick of `interval`.
+///
+/// If the target is still ahead, stops once it's reached and runs the command if there's one,
+/// exiting with its status. Otherwise keeps going until interrupted. Outside of a terminal, each
+/// change is printed on its own line instead.
+fn handle_watch<F>(
+    target: DateTime<Zone>,
+    interval: Duration,
+    command: Option<&str>,
+    render: F,
+) -> !
+where
+    F: Fn(DateTime<Zone>) -> String,
+{
+    let zone = target.timezone();
+    let is_terminal = io::stdout().is_terminal();
+    let is_ahead = zone.now() < target;
+    let mut previous = String::new();
+
+    loop {
+        let now = zone.now();
+        let is_reached = is_ahead && now >= target;
+        let output = render(if is_reached { target } else { now });
+
+        // `\x1b[K` clears whatever was left over from a longer line.
+        let written = if is_terminal {
+            write!(io::stdout(), "\r{}\x1b[K", output)
+        } else if output != previous {
+            writeln!(io::stdout(), "{}", output)
+        } else {
+            Ok(())
+        };
+        previous = output;
+        // Most likely a closed pipe, so nobody's listening anymore anyway.
+        if written.and_then(|_| io::stdout().flush()).is_err() {
+            process::exit(1);
+        }
+
+        if is_reached {
+            break;
+        }
+
+        // Never negative, so this can't fail.
+        if let Ok(wait) = until_next_tick(zone.now(), target, interval).to_std() {
+            thread::sleep(wait);
+        }
+    }
+
+    if is_terminal {
+        println!();
+    }
+
+    match command.map(|command| shell(command).status()) {
+        Some(Ok(status)) => process::exit(status.code().unwrap_or(1)),
+        Some(Err(err)) => {
+            eprintln!("Unable to run `{}`: {}.", command.unwrap_or_default(), err);
+            process::exit(1);
+        }
+        None => process::exit(0),
+    }
+}
+
+/// Build a command that runs the given line in the system shell.
+fn shell(command: &str) -> process::Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let mut shell = process::Command::new(shell);
+    shell.arg(flag).arg(command);
+    shell
+}
+
+/// Save the current datetime, or the given one, under a name.
+fn handle_mark(matches: &ArgMatches) {
+    // Validated by Clap already
+    let zone = matches
+        .value_of("tz")
+        .and_then(parse_zone)
+        .unwrap_or(Zone::Local);
+    let now = zone.now();
+    let parse_options = build_parse_options(matches);
+    // Required, so Clap makes sure it exists.
+    let name = matches.value_of("name").unwrap_or_default();
+
+    if let Err(err) = check_new_bookmark(name, now) {
+        eprintln!("Unable to save bookmark: {}.", err);
+        process::exit(1);
+    }
+
+    let datetime = match matches.value_of("datetime") {
+        Some(arg) => match try_parse_with_options(arg, now, &parse_options) {
+            Ok(datetime) => datetime,
+            Err(err) => {
+                eprintln!(
+                    "Unable to parse DATETIME arg `{}` into datetime: {}.",
+                    arg, err
+                );
+                print_parse_error(&err);
+                process::exit(1);
+            }
+        },
+        None => now,
+    };
+
+    let mut bookmarks = parse_options.bookmarks;
+    bookmarks.insert(name, datetime);
+    write_bookmarks(&bookmarks);
+
+    println!(
+        "Saved `{}` at {}.",
+        name,
+        datetime.format("%Y-%m-%d %H:%M:%S")
+    );
+}
+
+/// List, remove or rename the saved bookmarks.
+fn handle_marks(matches: &ArgMatches) {
+    let now = Zone::Local.now();
+    let mut bookmarks = read_bookmarks();
+
+    // Required, so Clap makes sure they exist.
+    match matches.subcommand() {
+        ("rm", Some(rm_matches)) => {
+            let name = rm_matches.value_of("name").unwrap_or_default();
+
+            if !bookmarks.remove(name) {
+                eprintln!("No bookmark named `{}`.", name);
+                process::exit(1);
+            }
+        }
+        ("rename", Some(rename_matches)) => {
+            let from = rename_matches.value_of("from").unwrap_or_default();
+            let to = rename_matches.value_of("to").unwrap_or_default();
+
+            if let Err(err) = check_new_bookmark(to, now) {
+                eprintln!("Unable to rename bookmark: {}.", err);
+                process::exit(1);
+            }
+            if !bookmarks.rename(from, to) {
+                if bookmarks.get(from, &Zone::Local).is_none() {
+                    eprintln!("No bookmark named `{}`.", from);
+                } else {
+                    eprintln!("Bookmark `{}` already exists.", to);
+                }
+                process::exit(1);
+            }
+        }
+        _ => {
+            let width = bookmarks.names().map(str::len).max().unwrap_or(0);
+            let options = OutputOptions {
+                signed: true,
+                ..OutputOptions::default()
+            };
+
+            for name in bookmarks.names() {
+                // Every listed name exists.
+                if let Some(datetime) = bookmarks.get(name, &now.timezone()) {
+                    println!(
+                        "{:width$}  {}  {}",
+                        name,
+                        datetime.format("%Y-%m-%d %H:%M:%S"),
+                        get_output(datetime, now, Filter::None, &options),
+                        width = width
+                    );
+                }
+            }
+            return;
+        }
+    }
+
+    write_bookmarks(&bookmarks);
+}
+
+/// Start, stop, lap, pause or resume the named timer, printing the times with the chosen unit.
+fn handle_timer(subcommand: &str, matches: &ArgMatches) {
+    let now = Zone::Local.now();
+    let mut timers = read_timers();
+    // Required, so Clap makes sure it exists.
+    let name = matches.value_of("name").unwrap_or_default();
+
+    let options = OutputOptions {
+        decimals: if matches.is_present("decimal") {
+            // Validated by Clap already
+            matches
+                .value_of("precision")
+                .and_then(|val| val.parse().ok())
+        } else {
+            None
+        },
+        ..OutputOptions::default()
+    };
+    let filter = Filter::from_str(matches.value_of("unit").unwrap_or_default());
+    // Times are durations rather than datetimes, so just count them back from now.
+    let render = |duration| get_output(now - duration, now, filter, &options);
+
+    if subcommand == START {
+        if timers.get(name).is_some() {
+            eprintln!("Timer `{}` is already running, stop it first.", name);
+            process::exit(1);
+        }
+
+        timers.insert(name, Timer::start(now));
+        write_timers(&timers);
+        println!("Started `{}`.", name);
+        return;
+    }
+
+    let timer = match timers.get_mut(name) {
+        Some(timer) => timer,
+        None => {
+            eprintln!(
+                "No timer named `{}`, start one with `since start {}`.",
+                name, name
+            );
+            process::exit(1);
+        }
+    };
+
+    match subcommand {
+        STOP => {
+            for (index, lap) in timer.laps().into_iter().enumerate() {
+                println!("Lap {}: {}", index + 1, render(lap));
+            }
+            println!("{}", render(timer.elapsed(now)));
+            timers.remove(name);
+        }
+        LAP => {
+            let lap = timer.lap(now);
+            println!(
+                "Lap {}: {} (total {})",
+                timer.laps().len(),
+                render(lap),
+                render(timer.elapsed(now))
+            );
+        }
+        PAUSE => {
+            if !timer.pause(now) {
+                eprintln!("Timer `{}` is already paused.", name);
+                process::exit(1);
+            }
+            println!("Paused `{}` at {}.", name, render(timer.elapsed(now)));
+        }
+        RESUME => {
+            if !timer.resume(now) {
+                eprintln!("Timer `{}` isn't paused.", name);
+                process::exit(1);
+            }
+            println!("Resumed `{}` at {}.", name, render(timer.elapsed(now)));
+        }
+        _ => unreachable!("`{}` isn't a timer subcommand", subcommand),
+    }
+
+    write_timers(&timers);
+}
+
+/// Load the saved timers, exiting if the file is broken.
+fn read_timers() -> Timers {
+    match timers_path().map(|path| (load_timers(&path), path)) {
+        Some((Ok(timers), _)) => timers,
+        Some((Err(err), path)) => {
+            eprintln!("Unable to read timers `{}`: {}.", path.display(), err);
+            process::exit(1);
+        }
+        None => Timers::default(),
+    }
+}
+
+/// Save the timers, exiting if that's not possible.
+fn write_timers(timers: &Timers) {
+    let path = match timers_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("Unable to find a place for timers, set $XDG_DATA_HOME or $HOME.");
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = save_timers(&path, timers) {
+        eprintln!("Unable to write timers `{}`: {}.", path.display(), err);
+        process::exit(1);
+    }
+}
+
+/// Read a datetime or a pair of them from each line of stdin, and print a result for each.
+///
+/// Lines are relative to the same `now`, and ordered like the arguments: `from [to]`, or
+/// `to [from]` for countdowns. Lines that can't be parsed print an error to stderr and an empty
+/// line (or a JSON error object) in place of the result, and exit with 1 at the end.
+fn handle_stdin<F>(
+    now: DateTime<Zone>,
+    countdown: bool,
+    parse_options: &ParseOptions,
+    is_json: bool,
+    render: F,
+) -> !
+where
+    F: Fn(DateTime<Zone>, DateTime<Zone>) -> String,
+{
+    let stdout = io::stdout();
+    let mut output = io::BufWriter::new(stdout.lock());
+    let mut has_errors = false;
+
+    for (index, line) in io::stdin().lock().lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("Unable to read stdin: {}.", err);
+                process::exit(1);
+            }
+        };
+
+        if let Some(warning) = check_ambiguity(&line, parse_options) {
+            eprintln!("Warning on line {}: {}", index + 1, warning);
+        }
+
+        let result = if line.trim().is_empty() {
+            String::new()
+        } else {
+            match try_parse_range(&line, now, parse_options) {
+                Ok((from, None)) if countdown => render(now, from),
+                Ok((from, None)) => render(from, now),
+                Ok((to, Some(from))) if countdown => render(from, to),
+                Ok((from, Some(to))) => render(from, to),
+                Err(err) => {
+                    has_errors = true;
+                    eprintln!("Unable to parse line {} into datetime: {}.", index + 1, err);
+
+                    if is_json {
+                        get_json_error_output(&err)
+                    } else {
+                        String::new()
+                    }
+                }
+            }
+        };
+
+        // Most likely a closed pipe, so nobody's listening anymore anyway.
+        if writeln!(output, "{}", result).is_err() {
+            break;
+        }
+    }
+
+    let _ = output.flush();
+    process::exit(if has_errors { 1 } else { 0 });
+}
+
+/// Print the duration of every interval in the timesheet, one per line, and the total of them at
+/// the end. The file is read from stdin if the path is `-`.
+///
+/// Each line is either a `from to` pair or any number of `from-to` ranges, see
+/// `try_parse_intervals`. Blank lines and `# comments` are skipped, and like in `handle_stdin`,
+/// lines that can't be parsed print an error and exit with 1 at the end.
+fn handle_timesheet<F>(
+    path: &str,
+    now: DateTime<Zone>,
+    parse_options: &ParseOptions,
+    is_json: bool,
+    render: F,
+) -> !
+where
+    F: Fn(DateTime<Zone>, DateTime<Zone>) -> String,
+{
+    let input: Box<dyn BufRead> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(path) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(err) => {
+                eprintln!("Unable to read timesheet `{}`: {}.", path, err);
+                process::exit(1);
+            }
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut output = io::BufWriter::new(stdout.lock());
+    let mut has_errors = false;
+    let mut total = Duration::zero();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("Unable to read timesheet `{}`: {}.", path, err);
+                process::exit(1);
+            }
+        };
 
-    let to: Arg = Arg::with_name("to")
-        .help("End time or date, for custom range. Default is current datetime.")
-        .required(false)
-        .index(2);
+        let line = line.split('#').next().unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(warning) = check_ambiguity(line, parse_options) {
+            eprintln!("Warning on line {}: {}", index + 1, warning);
+        }
+
+        match try_parse_intervals(line, now, parse_options) {
+            Ok(intervals) => {
+                for (from, to) in intervals {
+                    total = total + (from.max(to) - from.min(to));
+                    // Most likely a closed pipe, so nobody's listening anymore anyway.
+                    if writeln!(output, "{}", render(from, to)).is_err() {
+                        process::exit(1);
+                    }
+                }
+            }
+            Err(err) => {
+                has_errors = true;
+                eprintln!(
+                    "Unable to parse line {} into intervals: {}.",
+                    index + 1,
+                    err
+                );
+            }
+        }
+    }
+
+    let total = render(now - total, now);
+    let _ = if is_json {
+        writeln!(output, "{}", total)
+    } else {
+        writeln!(output, "Total: {}", total)
+    };
+    let _ = output.flush();
+    process::exit(if has_errors { 1 } else { 0 });
+}
+
+/// Describe how an ambiguous date in the argument is going to be read, unless refusing those.
+fn check_ambiguity(arg: &str, parse_options: &ParseOptions) -> Option<String> {
+    if parse_options.strict {
+        return None;
+    }
+
+    check_date_order(arg, parse_options.date_order)
+        .err()
+        .map(|warning| {
+            format!(
+                "{}, using the first one. Pass --date-order to choose.",
+                warning.reason()
+            )
+        })
+}
+
+/// Collect the parsing options from the arguments and the config file. Formats given on the
+/// command line are always tried first.
+fn build_parse_options(matches: &ArgMatches) -> ParseOptions {
+    let config = match config_path().map(|path| (load_config(&path), path)) {
+        Some((Ok(config), _)) => config,
+        Some((Err(err), path)) => {
+            eprintln!("Unable to read config `{}`: {}.", path.display(), err);
+            process::exit(1);
+        }
+        None => Config::default(),
+    };
+
+    let mut formats_first: Vec<String> = matches
+        .values_of("input-format")
+        .map(|formats| formats.map(String::from).collect())
+        .unwrap_or_default();
+    let mut formats_last = vec![];
+
+    if config.input_formats_first {
+        formats_first.extend(config.input_formats);
+    } else {
+        formats_last.extend(config.input_formats);
+    }
+
+    ParseOptions {
+        // Validated by Clap already
+        date_order: match matches.value_of("date-order") {
+            Some("mdy") => DateOrder::Mdy,
+            Some("ymd") => DateOrder::Ymd,
+            _ => DateOrder::Dmy,
+        },
+        strict: matches.is_present("strict"),
+        formats_first,
+        formats_last,
+        bookmarks: read_bookmarks(),
+    }
+}
+
+/// Load the saved bookmarks, exiting if the file is broken.
+fn read_bookmarks() -> Bookmarks {
+    match bookmarks_path().map(|path| (load_bookmarks(&path), path)) {
+        Some((Ok(bookmarks), _)) => bookmarks,
+        Some((Err(err), path)) => {
+            eprintln!("Unable to read bookmarks `{}`: {}.", path.display(), err);
+            process::exit(1);
+        }
+        None => Bookmarks::default(),
+    }
+}
+
+/// Save the bookmarks, exiting if that's not possible.
+fn write_bookmarks(bookmarks: &Bookmarks) {
+    let path = match bookmarks_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("Unable to find a place for bookmarks, set $XDG_DATA_HOME or $HOME.");
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = save_bookmarks(&path, bookmarks) {
+        eprintln!("Unable to write bookmarks `{}`: {}.", path.display(), err);
+        process::exit(1);
+    }
+}
+
+/// Check whether the name can be used for a bookmark or a timer. Names can't be empty or contain
+/// whitespace, since they're stored in tab-separated files and matched against whole arguments.
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        Err(format!("name `{}` can't be empty or contain spaces", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a new bookmark name wouldn't be taken as something else on the command line.
+fn check_new_bookmark(name: &str, now: DateTime<Zone>) -> Result<(), String> {
+    if let Some(subcommand) = subcommand_names()
+        .into_iter()
+        .find(|subcommand| subcommand.starts_with(name))
+    {
+        return Err(format!(
+            "`{}` would be read as the `{}` subcommand",
+            name, subcommand
+        ));
+    }
+
+    match try_parse_all_formats(name, now) {
+        Ok(_) => Err(format!("`{}` is already a valid datetime", name)),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Collect the working days and hours for `workdays` and `workhours` from the arguments.
+fn build_work_calendar(
+    matches: &ArgMatches,
+    now: DateTime<Zone>,
+    parse_options: &ParseOptions,
+) -> WorkCalendar {
+    let mut calendar = WorkCalendar::default();
+
+    // Validated by Clap already
+    if let Some(Ok((start, end))) = matches.value_of("work-hours").map(try_parse_work_hours) {
+        calendar.start = start;
+        calendar.end = end;
+    }
+
+    if let Some(path) = matches.value_of("holidays") {
+        calendar.holidays = match load_holidays(Path::new(path), now, parse_options) {
+            Ok(holidays) => holidays,
+            Err(err) => {
+                eprintln!("Unable to read holidays `{}`: {}.", path, err);
+                process::exit(1);
+            }
+        };
+    }
+
+    calendar
+}
+
+/// Point out where exactly parsing went wrong, with a suggestion if there's one.
+fn print_parse_error(err: &ParseError) {
+    let (start, end) = err.span();
+    let input = err.input();
+    // Columns are in characters, not bytes, so that the marker lines up in the terminal.
+    let offset = input[..start].chars().count();
+    let width = input[start..end].chars().count().max(1);
+
+    eprintln!();
+    eprintln!("    {}", input);
+    eprintln!("    {}{}", " ".repeat(offset), "^".repeat(width));
+
+    if let Some(suggestion) = err.suggestion() {
+        eprintln!();
+        eprintln!("Did you mean `{}`?", suggestion);
+    }
+}
+
+/// Handle the given matches for either the root command or `until`, both of which share the same
+/// filtering subcommands.
+fn handle_subcommands(matches: &ArgMatches, countdown: bool) {
+    match matches.subcommand() {
+        (subcmd, Some(sub_matches)) => {
+            handle_args(Filter::from_str(subcmd), sub_matches, countdown)
+        }
+        _ => handle_args(Filter::None, matches, countdown),
+    };
+}
+
+/// Build the arguments shared by the root command and all subcommands.
+///
+/// Countdowns take the target as their first argument, and count from the current datetime
+/// unless told otherwise.
+fn build_args<'a, 'b>(countdown: bool) -> Vec<Arg<'a, 'b>> {
+    let from: Arg = if countdown {
+        Arg::with_name("from")
+            .help("Start time or date, for custom range. Default is current datetime.")
+            .required(false)
+            .index(2)
+    } else {
+        Arg::with_name("from")
+            .help("Start time or date.")
+            .required(false)
+            .index(1)
+    };
+
+    let to: Arg = if countdown {
+        Arg::with_name("to")
+            .help("Target time or date.")
+            .required_unless_one(&["stdin", "timesheet"])
+            .index(1)
+    } else {
+        Arg::with_name("to")
+            .help("End time or date, for custom range. Default is current datetime.")
+            .required(false)
+            .index(2)
+    };
+
+    let (decimal, precision) = build_decimal_args();
+
+    let signed: Arg = Arg::with_name("signed")
+        .help("Keep the direction of the difference. Exits with 2 if <from> is after <to>.")
+        .short("s")
+        .long("signed");
+
+    let stdin: Arg = Arg::with_name("stdin")
+        .help("Read a datetime or a pair of them from each line of stdin, printing one result per line.")
+        .long("stdin")
+        .conflicts_with_all(&["from", "to"]);
+
+    let timesheet: Arg = Arg::with_name("timesheet")
+        .help(
+            "Print the duration of each interval in the file, eg. '09:00-12:30 13:15-17:45' on \
+             each line, and the total. Use - for stdin.",
+        )
+        .short("t")
+        .long("timesheet")
+        .value_name("FILE")
+        .conflicts_with_all(&["from", "to", "stdin"]);
+
+    let watch: Arg = Arg::with_name("watch")
+        .help(
+            "Keep redrawing the output until interrupted, or until the target is reached if it's \
+             still ahead.",
+        )
+        .short("w")
+        .long("watch")
+        .conflicts_with_all(&["stdin", "timesheet"]);
+
+    let interval: Arg = Arg::with_name("interval")
+        .help("Seconds between redraws with --watch. [default: 1]")
+        .short("n")
+        .long("interval")
+        .value_name("SECONDS")
+        .requires("watch")
+        .validator(|val| match val.parse::<f64>() {
+            Ok(seconds) if seconds >= 0.01 => Ok(()),
+            _ => Err(String::from("interval must be at least 0.01 seconds")),
+        });
+
+    let exec: Arg = Arg::with_name("exec")
+        .help("Command to run once --watch reaches the target.")
+        .short("x")
+        .long("exec")
+        .value_name("COMMAND")
+        .requires("watch");
+
+    let format: Arg = Arg::with_name("format")
+        .help(
+            "Output format. JSON and ISO 8601 durations contain every unit regardless of the \
+             subcommand.",
+        )
+        .short("f")
+        .long("format")
+        .value_name("FORMAT")
+        .possible_values(&["text", "json", "iso"])
+        .default_value("text");
+
+    let output_format: Arg = Arg::with_name("output-format")
+        .help("Template for the output, eg. '{d}d {h}h {m}m' or '%H:%M:%S'. See the README.")
+        .short("o")
+        .long("output-format")
+        .value_name("TEMPLATE")
+        .validator(|val| parse_template(&val).map(|_| ()));
+
+    let mut args = vec![
+        from,
+        to,
+        decimal,
+        precision,
+        signed,
+        stdin,
+        timesheet,
+        watch,
+        interval,
+        exec,
+        format,
+        output_format,
+    ];
+    args.extend(build_parse_args());
+    args
+}
+
+/// Build the `--decimal` and `--precision` arguments, shared with the timers.
+fn build_decimal_args<'a, 'b>() -> (Arg<'a, 'b>, Arg<'a, 'b>) {
+    let decimal: Arg = Arg::with_name("decimal")
+        .help("Print fractional values instead of rounding down.")
+        .short("d")
+        .long("decimal");
+
+    let precision: Arg = Arg::with_name("precision")
+        .help("Number of decimals to print with --decimal.")
+        .short("p")
+        .long("precision")
+        .value_name("DIGITS")
+        .default_value("2")
+        .validator(|val| match val.parse::<usize>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(String::from("precision must be a positive integer")),
+        });
+
+    (decimal, precision)
+}
+
+/// Build the arguments that affect how inputs are parsed, shared by every subcommand.
+fn build_parse_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let tz: Arg = Arg::with_name("tz")
+        .help("Timezone for naive inputs and the current datetime. [default: local]")
+        .long("tz")
+        .value_name("ZONE")
+        .validator(|val| match parse_zone(&val) {
+            Some(_) => Ok(()),
+            None => Err(format!("unknown timezone `{}`", val)),
+        });
+
+    let date_order: Arg = Arg::with_name("date-order")
+        .help("Order of numeric dates like 03/04/2020: day, month or year first.")
+        .long("date-order")
+        .value_name("ORDER")
+        .env("SINCE_DATE_ORDER")
+        .possible_values(&["dmy", "mdy", "ymd"])
+        .default_value("dmy");
+
+    let strict: Arg = Arg::with_name("strict")
+        .help("Refuse dates that could be read both day and month first, instead of warning.")
+        .long("strict");
+
+    let input_format: Arg = Arg::with_name("input-format")
+        .help("Extra strftime format for the inputs, eg. '%d%m%Y-%H%M'. Tried before the others.")
+        .short("i")
+        .long("input-format")
+        .value_name("FORMAT")
+        .multiple(true)
+        .number_of_values(1);
+
+    vec![tz, date_order, strict, input_format]
+}
+
+/// Build the arguments for the business time subcommands.
+fn build_work_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let holidays: Arg = Arg::with_name("holidays")
+        .help("File with a holiday date on each line, skipped like weekends.")
+        .long("holidays")
+        .value_name("FILE");
+
+    let work_hours: Arg = Arg::with_name("work-hours")
+        .help("Working hours of each weekday.")
+        .long("work-hours")
+        .value_name("START-END")
+        .default_value("09:00-17:00")
+        .validator(|val| try_parse_work_hours(&val).map(|_| ()));
+
+    vec![holidays, work_hours]
+}
+
+/// Build the subcommands for adding a duration to a datetime and subtracting one from it.
+fn build_shift_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    let args = |about| {
+        vec![
+            Arg::with_name("datetime")
+                .help(about)
+                .required(true)
+                .index(1),
+            Arg::with_name("duration")
+                .help("Duration, eg. '3 weeks 2 days', 90d, 1h30m or P1Y2M.")
+                .required(true)
+                .index(2),
+        ]
+    };
+
+    vec![
+        SubCommand::with_name(ADD)
+            .about("Print the datetime a duration after <datetime>")
+            .args(&args("Time or date to start from, eg. now."))
+            .args(&build_parse_args()),
+        SubCommand::with_name(SUB)
+            .about("Print the datetime a duration before <datetime>")
+            .args(&args("Time or date to start from, eg. now."))
+            .args(&build_parse_args()),
+    ]
+}
+
+/// Build the subcommands for saving bookmarks and managing them.
+fn build_mark_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    let name = |name, help| {
+        Arg::with_name(name)
+            .help(help)
+            .required(true)
+            .validator(|val| check_name(&val))
+    };
+
+    vec![
+        SubCommand::with_name(MARK)
+            .about("Save the current datetime under <name>, usable in place of any input")
+            .arg(name("name", "Name of the bookmark, eg. deploy.").index(1))
+            .arg(
+                Arg::with_name("datetime")
+                    .help("Time or date to save instead of the current one.")
+                    .index(2),
+            )
+            .args(&build_parse_args()),
+        SubCommand::with_name(MARKS)
+            .about("List, remove or rename the saved bookmarks")
+            .setting(AppSettings::DisableHelpSubcommand)
+            .setting(AppSettings::VersionlessSubcommands)
+            .subcommand(
+                SubCommand::with_name("list").about("List the bookmarks and the time since each"),
+            )
+            .subcommand(
+                SubCommand::with_name("rm")
+                    .about("Remove a bookmark")
+                    .arg(name("name", "Bookmark to remove.").index(1)),
+            )
+            .subcommand(
+                SubCommand::with_name("rename")
+                    .about("Rename a bookmark")
+                    .arg(name("from", "Current name of the bookmark.").index(1))
+                    .arg(name("to", "New name for the bookmark.").index(2)),
+            ),
+    ]
+}
+
+/// Build the stopwatch subcommands, which all take the name of the timer and output options.
+fn build_timer_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    let (decimal, precision) = build_decimal_args();
+    let args = vec![
+        Arg::with_name("name")
+            .help("Name of the timer, eg. qa.")
+            .required(true)
+            .index(1)
+            .validator(|val| check_name(&val)),
+        Arg::with_name("unit")
+            .help("Print the time in this unit, like the subcommands of the same name.")
+            .short("u")
+            .long("unit")
+            .value_name("UNIT")
+            .possible_values(&[
+                Filter::Years.as_str(),
+                Filter::Months.as_str(),
+                Filter::Weeks.as_str(),
+                Filter::Days.as_str(),
+                Filter::Hours.as_str(),
+                Filter::Minutes.as_str(),
+                Filter::Seconds.as_str(),
+                Filter::Milliseconds.as_str(),
+                Filter::Microseconds.as_str(),
+                Filter::Nanoseconds.as_str(),
+                Filter::Full.as_str(),
+                Filter::Clock.as_str(),
+            ]),
+        decimal,
+        precision,
+    ];
+
+    vec![
+        SubCommand::with_name(START)
+            .about("Start a stopwatch that's kept running between shells")
+            .args(&args),
+        SubCommand::with_name(STOP)
+            .about("Print the laps and the total time of a stopwatch, and remove it")
+            .args(&args),
+        SubCommand::with_name(LAP)
+            .about("Print the time since the previous lap of a stopwatch, and the total")
+            .args(&args),
+        SubCommand::with_name(PAUSE)
+            .about("Pause a stopwatch")
+            .args(&args),
+        SubCommand::with_name(RESUME)
+            .about("Resume a paused stopwatch")
+            .args(&args),
+    ]
+}
+
+/// Names of every subcommand, which bookmarks can't start with or they'd be taken as one.
+fn subcommand_names() -> Vec<String> {
+    build_subcommands(&[])
+        .iter()
+        .chain(&build_shift_subcommands())
+        .chain(&build_mark_subcommands())
+        .chain(&build_timer_subcommands())
+        .map(|subcommand| subcommand.get_name().to_string())
+        .chain(Some(UNTIL.to_string()))
+        .collect()
+}
+
+/// Build all the output filtering subcommands, each with the given shared arguments.
+fn build_subcommands<'a, 'b>(args: &[Arg<'a, 'b>]) -> Vec<App<'a, 'b>> {
+    let abbreviations = ABBREVIATIONS.iter().map(|(abbreviation, _)| {
+        SubCommand::with_name(abbreviation)
+            .setting(AppSettings::Hidden)
+            .args(args)
+    });
+
+    let mut subcommands = vec![
+        SubCommand::with_name(Filter::Years.as_str())
+            .about("Print the output in full years")
+            .args(args),
+        SubCommand::with_name(Filter::Months.as_str())
+            .about("Print the output in full months")
+            .args(args),
+        SubCommand::with_name(Filter::Weeks.as_str())
+            .about("Print the output in weeks (approx)")
+            .args(args),
+        SubCommand::with_name(Filter::Days.as_str())
+            .about("Print the output in days")
+            .args(args),
+        SubCommand::with_name(Filter::Hours.as_str())
+            .about("Print the output in hours")
+            .args(args),
+        SubCommand::with_name(Filter::Minutes.as_str())
+            .about("Print the output in minutes")
+            .args(args),
+        SubCommand::with_name(Filter::Seconds.as_str())
+            .about("Print the output in seconds")
+            .args(args),
+        SubCommand::with_name(Filter::Milliseconds.as_str())
+            .about("Print the output in milliseconds")
+            .args(args),
+        SubCommand::with_name(Filter::Microseconds.as_str())
+            .about("Print the output in microseconds")
+            .args(args),
+        SubCommand::with_name(Filter::Nanoseconds.as_str())
+            .about("Print the output in nanoseconds")
+            .args(args),
+        SubCommand::with_name(Filter::Full.as_str())
+            .about("Print the output as an exhaustive breakdown of all units")
+            .args(args),
+        SubCommand::with_name(Filter::Clock.as_str())
+            .about("Print the output as a clock, [D-]HH:MM:SS. Use --decimal for fractions")
+            .args(args),
+        SubCommand::with_name(Filter::Workdays.as_str())
+            .about("Print the output in working days, skipping weekends and holidays")
+            .args(args)
+            .args(&build_work_args()),
+        SubCommand::with_name(Filter::Workhours.as_str())
+            .about("Print the output in working hours, skipping weekends and holidays")
+            .args(args)
+            .args(&build_work_args()),
+    ];
+    subcommands.extend(abbreviations);
+    subcommands
+}
+
+fn main() {
+    // Allow symlinking `since` -> `until` for countdowns.
+    let invoked_as_until = env::args_os()
+        .next()
+        .and_then(|arg| Path::new(&arg).file_stem().map(|name| name == UNTIL))
+        .unwrap_or(false);
 
     let about = "
 Fetch time difference between <from> and <to>.
@@ -65,62 +1205,60 @@ command, just filtering the output to different format.
 
 All values are generally rounded down.";
 
-    let matches = App::new("since")
-        .about(about)
+    let until_about = "
+Count down the time from now (or <from>) until <to>.
+
+Exits with 2 and prints a warning if <to> is already in the past.
+All subcommands share exactly the same functionality and arguments as base
+command, just filtering the output to different format.
+
+All values are generally rounded down.";
+
+    let args = build_args(invoked_as_until);
+    let until_args = build_args(true);
+
+    let app = if invoked_as_until {
+        App::new(UNTIL).about(until_about)
+    } else {
+        App::new("since")
+            .about(about)
+            .subcommand(
+                SubCommand::with_name(UNTIL)
+                    .about("Count down the time until <to>")
+                    .setting(AppSettings::InferSubcommands)
+                    .setting(AppSettings::DisableHelpSubcommand)
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .setting(AppSettings::ArgsNegateSubcommands)
+                    .setting(AppSettings::SubcommandsNegateReqs)
+                    .args(&until_args)
+                    .subcommands(build_subcommands(&until_args)),
+            )
+            .subcommands(build_shift_subcommands())
+            .subcommands(build_mark_subcommands())
+            .subcommands(build_timer_subcommands())
+    };
+
+    let matches = app
         .version("v0.11.0")
         .setting(AppSettings::InferSubcommands)
         .setting(AppSettings::VersionlessSubcommands)
         .setting(AppSettings::DisableHelpSubcommand)
         .setting(AppSettings::DeriveDisplayOrder)
         .setting(AppSettings::ArgsNegateSubcommands)
-        .arg(&from)
-        .arg(&to)
-        .subcommand(
-            SubCommand::with_name(Filter::Years.as_str())
-                .about("Print the output in years (approx)")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Months.as_str())
-                .about("Print the output in months (approx)")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Weeks.as_str())
-                .about("Print the output in weeks (approx)")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Days.as_str())
-                .about("Print the output in days")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Hours.as_str())
-                .about("Print the output in hours")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Minutes.as_str())
-                .about("Print the output in minutes")
-                .arg(&from)
-                .arg(&to),
-        )
-        .subcommand(
-            SubCommand::with_name(Filter::Seconds.as_str())
-                .about("Print the output in seconds")
-                .arg(&from)
-                .arg(&to),
-        )
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .args(&args)
+        .subcommands(build_subcommands(&args))
         .get_matches();
 
     match matches.subcommand() {
-        (subcmd, Some(sub_matches)) => handle_args(Filter::from_str(subcmd), sub_matches),
-        _ => handle_args(Filter::None, &matches),
+        (UNTIL, Some(until_matches)) => handle_subcommands(until_matches, true),
+        (ADD, Some(add_matches)) => handle_shift(add_matches, false),
+        (SUB, Some(sub_matches)) => handle_shift(sub_matches, true),
+        (MARK, Some(mark_matches)) => handle_mark(mark_matches),
+        (MARKS, Some(marks_matches)) => handle_marks(marks_matches),
+        (subcmd @ (START | STOP | LAP | PAUSE | RESUME), Some(timer_matches)) => {
+            handle_timer(subcmd, timer_matches)
+        }
+        _ => handle_subcommands(&matches, invoked_as_until),
     };
 }
@@ -1,92 +1,885 @@
-use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, ParseError, TimeZone, Timelike};
+use crate::bookmarks::Bookmarks;
+use crate::calculators::{shift_datetime, Breakdown};
+use crate::diagnostics::{closest_word, diagnose};
+use crate::errors::ParseError;
+use crate::subcommands::Filter;
+use crate::zones::{parse_zone, Zone};
+use chrono::format::ParseResult;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+/// Order of the day, month and year in numeric dates, eg. `03/04/2020`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DateOrder {
+    /// Day first, eg. `24.12.2019`.
+    #[default]
+    Dmy,
+    /// Month first, as in the US, eg. `12/24/2019`.
+    Mdy,
+    /// Year first only, eg. `2019/12/24`.
+    Ymd,
+}
+
+/// Options for `try_parse_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// How to read numeric dates that don't start with the year. Year-first dates are always
+    /// accepted, since nobody writes year-day-month.
+    pub date_order: DateOrder,
+    /// Refuse dates that could be read in more than one order, instead of going with `date_order`.
+    pub strict: bool,
+    /// Extra strftime formats, tried before any of the built-in ones.
+    pub formats_first: Vec<String>,
+    /// Extra strftime formats, tried only if none of the built-in ones match.
+    pub formats_last: Vec<String>,
+    /// Named datetimes, matched against the whole input before anything else.
+    pub bookmarks: Bookmarks,
+}
 
 /// Eager datetime parsing for given arguments, testing multiple date and time formats and only
 /// failing if absolutely nothing matches.
-pub fn try_parse_all_formats(
+///
+/// Inputs without an explicit timezone are interpreted in the timezone of `now`, and all results
+/// are converted into it.
+pub fn try_parse_all_formats(arg: &str, now: DateTime<Zone>) -> Result<DateTime<Zone>, ParseError> {
+    try_parse_with_options(arg, now, &ParseOptions::default())
+}
+
+/// Same as `try_parse_all_formats`, but with control over ambiguous inputs.
+pub fn try_parse_with_options(
     arg: &str,
-    now: DateTime<Local>,
-) -> Result<DateTime<Local>, ParseError> {
-    try_parse_times(arg, &now)
-        .or_else(|_err| try_parse_dates(arg, &now))
-        .or_else(|_err| try_parse_datetimes(arg))
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Result<DateTime<Zone>, ParseError> {
+    if let Some(datetime) = options.bookmarks.get(arg.trim(), &now.timezone()) {
+        return Ok(datetime);
+    }
+
+    if options.strict {
+        check_date_order(arg, options.date_order)?;
+    }
+
+    let mut date_formats = with_date_order(DATE_FORMATS, options.date_order);
+    let mut datetime_formats = with_date_order(DATETIME_FORMATS, options.date_order);
+
+    // chrono reads `%j` with anything from one to three digits, which would turn eg. `2020-06` into
+    // the 6th of January.
+    if !has_ordinal_day(arg) {
+        date_formats.retain(|format| !format.contains("%j"));
+        datetime_formats.retain(|format| !format.contains("%j"));
+    }
+
+    if let Some(datetime) = try_parse_custom(arg, &options.formats_first, &now) {
+        return Ok(datetime.with_timezone(&now.timezone()));
+    }
+
+    try_parse_rfc_formats(arg)
+        .or_else(|err| try_parse_epoch(arg, &now.timezone()).ok_or(err))
+        .or_else(|err| {
+            let (arg, zone) = split_zone(arg);
+            let now_in_zone = now.with_timezone(&zone.unwrap_or_else(|| now.timezone()));
+
+            try_parse_times(arg, TIME_FORMATS, &now_in_zone)
+                .or_else(|| try_parse_dates(arg, &date_formats, &now_in_zone))
+                .or_else(|| {
+                    try_parse_datetimes(arg, &datetime_formats, &now_in_zone.timezone()).ok()
+                })
+                .or_else(|| try_parse_relative(arg, &now_in_zone))
+                .ok_or(err)
+        })
+        .or_else(|err| try_parse_custom(arg, &options.formats_last, &now).ok_or(err))
+        .map(|datetime| datetime.with_timezone(&now.timezone()))
+        .map_err(|_err| {
+            // Diagnose without the timezone, but keep it in the suggestion.
+            let (rest, _zone) = split_zone(arg);
+            let formats: Vec<&str> = options
+                .formats_first
+                .iter()
+                .map(String::as_str)
+                .chain(TIME_FORMATS.iter().copied())
+                .chain(
+                    date_formats
+                        .iter()
+                        .chain(&datetime_formats)
+                        .chain(&options.formats_last)
+                        .map(String::as_str),
+                )
+                .collect();
+            let error = diagnose(rest, &formats);
+
+            // Nothing else looked close, so maybe it's a typo of a bookmark instead.
+            if error.suggestion.is_none() && error.closest_format.is_none() {
+                let names: Vec<&str> = options.bookmarks.names().collect();
+                if let Some(name) = closest_word(arg.trim(), &names) {
+                    return ParseError {
+                        reason: format!("unknown bookmark `{}`", arg.trim()),
+                        suggestion: Some(name.to_string()),
+                        ..error
+                    };
+                }
+            }
+
+            ParseError {
+                input: arg.to_string(),
+                suggestion: error
+                    .suggestion
+                    .map(|suggestion| suggestion + &arg[rest.len()..]),
+                ..error
+            }
+        })
 }
 
-/// Tries to parse given argument through basic timestamp formats and create a locale-aware current
-/// datetime using the provided `now`.
-fn try_parse_times(arg: &str, now: &DateTime<Local>) -> Result<DateTime<Local>, ParseError> {
-    NaiveTime::parse_from_str(&arg, "%T")
-        .or_else(|_err| NaiveTime::parse_from_str(&arg, "%R"))
-        .and_then(|val| {
-            Ok(Local.ymd(now.year(), now.month(), now.day()).and_hms(
-                val.hour(),
-                val.minute(),
-                val.second(),
-            ))
+/// Parse a line of either a single datetime or a `from to` pair of them, eg. from a file or stdin.
+///
+/// Since datetimes can contain spaces themselves, the pair is split on a tab if there's one.
+/// Otherwise the whole line is tried as a single datetime first, and then split on each space in
+/// turn until both sides parse.
+pub fn try_parse_range(
+    line: &str,
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Result<(DateTime<Zone>, Option<DateTime<Zone>>), ParseError> {
+    let line = line.trim();
+
+    if let Some((from, to)) = line.split_once('\t') {
+        let from = try_parse_with_options(from.trim(), now, options)?;
+        let to = try_parse_with_options(to.trim(), now, options)?;
+        return Ok((from, Some(to)));
+    }
+
+    try_parse_with_options(line, now, options)
+        .map(|datetime| (datetime, None))
+        .or_else(|err| {
+            line.match_indices(' ')
+                .find_map(|(index, _)| {
+                    let from = try_parse_with_options(line[..index].trim(), now, options).ok()?;
+                    let to = try_parse_with_options(line[index..].trim(), now, options).ok()?;
+                    Some((from, Some(to)))
+                })
+                .ok_or(err)
         })
 }
 
-/// Tries to parse given argument through multiple different date formats and create a locale-aware
-/// current datetime using the provided `now`.
-fn try_parse_dates(arg: &str, now: &DateTime<Local>) -> Result<DateTime<Local>, ParseError> {
-    // Try to go through the formats in the order of (entirely subjective) "commonness"
-    NaiveDate::parse_from_str(&arg, "%Y-%m-%d")
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%Y/%m/%d"))
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%Y.%m.%d"))
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%d-%m-%Y"))
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%d/%m/%Y"))
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%d.%m.%Y"))
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%Y %B %d")) // %B == July || Jul
-        .or_else(|_err| NaiveDate::parse_from_str(&arg, "%d %B %Y"))
-        .and_then(|val| {
-            Ok(Local.ymd(val.year(), val.month(), val.day()).and_hms(
-                now.hour(),
-                now.minute(),
-                now.second(),
-            ))
+/// Start and end of a single timesheet entry.
+pub type Interval = (DateTime<Zone>, DateTime<Zone>);
+
+/// Parse a line of a timesheet into intervals: either any number of `from-to` ranges separated
+/// by spaces, eg. `09:00-12:30 13:15-17:45`, or a single `from to` pair like in `try_parse_range`.
+///
+/// Ranges of two times that end before they start are taken to go past midnight, eg.
+/// `22:00-02:00`. Any other range that ends before it starts is an error.
+pub fn try_parse_intervals(
+    line: &str,
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Result<Vec<Interval>, ParseError> {
+    let ranges: Vec<(&str, Option<Result<Interval, String>>)> = line
+        .split_whitespace()
+        .map(|range| (range, try_parse_dash_range(range, now, options)))
+        .collect();
+
+    // Once there's a single range, the rest of the line has to be ranges as well.
+    if ranges.iter().any(|(_, interval)| interval.is_some()) {
+        return ranges
+            .into_iter()
+            .map(|(range, interval)| {
+                let reason = match interval {
+                    Some(Ok(interval)) => return Ok(interval),
+                    Some(Err(reason)) => reason,
+                    None => format!("expected a `from-to` range, got `{}`", range),
+                };
+
+                // Always a part of the line, so this is its position there.
+                let position = range.as_ptr() as usize - line.as_ptr() as usize;
+                Err(ParseError {
+                    input: line.to_string(),
+                    closest_format: None,
+                    position,
+                    length: range.len(),
+                    reason,
+                    suggestion: None,
+                })
+            })
+            .collect();
+    }
+
+    match try_parse_range(line, now, options)? {
+        (from, Some(to)) => Ok(vec![(from, to)]),
+        (_, None) => Err(ParseError {
+            input: line.trim().to_string(),
+            closest_format: None,
+            position: 0,
+            length: line.trim().len(),
+            reason: String::from("expected a `from to` pair or `from-to` ranges"),
+            suggestion: None,
+        }),
+    }
+}
+
+/// Split a `from-to` range on the first dash that leaves a valid datetime on both sides, since
+/// the datetimes can have dashes of their own. Returns `None` if it isn't a range at all, and the
+/// reason if it ends before it starts without being two times across midnight.
+fn try_parse_dash_range(
+    range: &str,
+    now: DateTime<Zone>,
+    options: &ParseOptions,
+) -> Option<Result<Interval, String>> {
+    range.match_indices('-').find_map(|(index, _)| {
+        let (from_arg, to_arg) = (&range[..index], &range[index + 1..]);
+        let from = try_parse_with_options(from_arg, now, options).ok()?;
+        let to = try_parse_with_options(to_arg, now, options).ok()?;
+
+        if to >= from {
+            return Some(Ok((from, to)));
+        }
+
+        let backwards = || Err(format!("range `{}` ends before it starts", range));
+        if !is_time_input(from_arg) || !is_time_input(to_arg) {
+            return Some(backwards());
+        }
+
+        match shift_datetime(to, 1, Filter::Days) {
+            Some(to) if to.signed_duration_since(from) < Duration::days(1) => Some(Ok((from, to))),
+            _ => Some(backwards()),
+        }
+    })
+}
+
+/// Whether the argument is a bare time of day in one of the built-in formats, without a date.
+fn is_time_input(arg: &str) -> bool {
+    let (arg, _zone) = split_zone(arg.trim());
+
+    TIME_FORMATS
+        .iter()
+        .any(|format| NaiveTime::parse_from_str(arg, format).is_ok())
+}
+
+/// Parse a duration for moving datetimes around, eg. `3 weeks 2 days`, `90d`, `1h30m`,
+/// `a year and 2 months` or an ISO 8601 duration like `P1Y2M3DT4H`.
+///
+/// Weeks are counted as 7 days, the rest of the units are kept as they are so that adding them
+/// can follow the calendar.
+pub fn try_parse_duration(arg: &str) -> Result<Breakdown, ParseError> {
+    if let Some(duration) = try_parse_iso_duration(arg) {
+        return duration;
+    }
+
+    let lowercase = arg.to_ascii_lowercase();
+    let error = |position: usize, length: usize, reason: String| ParseError {
+        input: arg.to_string(),
+        closest_format: None,
+        position,
+        length,
+        reason,
+        suggestion: None,
+    };
+
+    let mut duration = Breakdown::default();
+    let mut position = 0;
+    let mut is_empty = true;
+
+    loop {
+        // Anything goes between the units, as long as it reads nicely.
+        let rest = &lowercase[position..];
+        let separators = rest.len() - rest.trim_start_matches([' ', ',']).len();
+        position += separators;
+        let rest = &lowercase[position..];
+        if let Some(after) = rest.strip_prefix("and ") {
+            position += rest.len() - after.len();
+            continue;
+        }
+        if rest.is_empty() {
+            break;
+        }
+
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (amount, length) = match (&rest[..digits], rest) {
+            ("", rest) if rest.starts_with("an ") => (1, 2),
+            ("", rest) if rest.starts_with("a ") => (1, 1),
+            ("", _) => {
+                let length = rest.find(' ').unwrap_or(rest.len());
+                return Err(error(
+                    position,
+                    length,
+                    String::from("expected an amount, eg. `3 days` or `3d`"),
+                ));
+            }
+            (digits, _) => match digits.parse::<i64>() {
+                Ok(amount) => (amount, digits.len()),
+                Err(_) => {
+                    let reason = String::from("amount is too large");
+                    return Err(error(position, digits.len(), reason));
+                }
+            },
+        };
+        let amount_span = (position, length);
+        position += length;
+
+        let rest = &lowercase[position..];
+        position += rest.len() - rest.trim_start().len();
+        let rest = &lowercase[position..];
+        let length = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .len();
+        let unit = &rest[..length];
+
+        let (value, multiplier) = match parse_unit(unit) {
+            Some(Filter::Years) => (&mut duration.years, 1),
+            Some(Filter::Months) => (&mut duration.months, 1),
+            Some(Filter::Weeks) => (&mut duration.days, 7),
+            Some(Filter::Days) => (&mut duration.days, 1),
+            Some(Filter::Hours) => (&mut duration.hours, 1),
+            Some(Filter::Minutes) => (&mut duration.minutes, 1),
+            Some(Filter::Seconds) => (&mut duration.seconds, 1),
+            _ if unit.is_empty() => {
+                return Err(error(
+                    position,
+                    rest.chars().next().map_or(0, char::len_utf8),
+                    format!("expected a unit after `{}`", amount),
+                ))
+            }
+            _ => {
+                let units = [
+                    "years", "months", "weeks", "days", "hours", "minutes", "seconds",
+                ];
+                let reason = format!("unknown unit `{}`", &arg[position..position + length]);
+                return Err(ParseError {
+                    suggestion: closest_word(unit, &units).map(|unit| {
+                        format!("{}{}{}", &arg[..position], unit, &arg[position + length..])
+                    }),
+                    ..error(position, length, reason)
+                });
+            }
+        };
+        *value = match amount
+            .checked_mul(multiplier)
+            .and_then(|amount| value.checked_add(amount))
+        {
+            Some(value) => value,
+            None => {
+                let reason = String::from("amount is too large");
+                return Err(error(amount_span.0, amount_span.1, reason));
+            }
+        };
+        position += length;
+        is_empty = false;
+    }
+
+    if is_empty {
+        return Err(error(
+            0,
+            arg.len(),
+            String::from("expected a duration, eg. `3 weeks 2 days` or `90d`"),
+        ));
+    }
+
+    Ok(duration)
+}
+
+/// Tries to parse given argument as an ISO 8601 duration, eg. `P1Y2M3DT4H5M6S` or `P2W`. Returns
+/// `None` if it isn't one, and an error if it is but the amounts are too large.
+fn try_parse_iso_duration(arg: &str) -> Option<Result<Breakdown, ParseError>> {
+    // A bare `P` isn't a duration.
+    if arg.len() < 2 {
+        return None;
+    }
+
+    let (date, time) = match arg.strip_prefix('P')?.split_once('T') {
+        Some((_, "")) => return None,
+        Some((date, time)) => (date, Some(time)),
+        None => (arg.strip_prefix('P')?, None),
+    };
+
+    let mut duration = Breakdown::default();
+    let mut parse_units = |part: &str, is_time: bool| -> Option<Result<(), ParseError>> {
+        let mut rest = part;
+
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+            if digits == 0 {
+                return None;
+            }
+
+            let (value, multiplier) = match (is_time, rest[digits..].chars().next()?) {
+                (false, 'Y') => (&mut duration.years, 1),
+                (false, 'M') => (&mut duration.months, 1),
+                (false, 'W') => (&mut duration.days, 7),
+                (false, 'D') => (&mut duration.days, 1),
+                (true, 'H') => (&mut duration.hours, 1),
+                (true, 'M') => (&mut duration.minutes, 1),
+                (true, 'S') => (&mut duration.seconds, 1),
+                _ => return None,
+            };
+            *value = match rest[..digits]
+                .parse::<i64>()
+                .ok()
+                .and_then(|amount| amount.checked_mul(multiplier))
+                .and_then(|amount| value.checked_add(amount))
+            {
+                Some(value) => value,
+                None => {
+                    return Some(Err(ParseError {
+                        input: arg.to_string(),
+                        closest_format: None,
+                        position: rest.as_ptr() as usize - arg.as_ptr() as usize,
+                        length: digits,
+                        reason: String::from("amount is too large"),
+                        suggestion: None,
+                    }));
+                }
+            };
+            rest = &rest[digits + 1..];
+        }
+
+        Some(Ok(()))
+    };
+
+    if let Err(err) = parse_units(date, false)? {
+        return Some(Err(err));
+    }
+    if let Some(time) = time {
+        if let Err(err) = parse_units(time, true)? {
+            return Some(Err(err));
+        }
+    }
+
+    Some(Ok(duration))
+}
+
+/// Parse working hours like `09:00-17:00` or `9-17` into the start and end of the working day.
+pub fn try_parse_work_hours(arg: &str) -> Result<(NaiveTime, NaiveTime), String> {
+    let parse_time = |time: &str| {
+        let time = time.trim();
+        match time.parse::<u32>() {
+            Ok(hour) => NaiveTime::from_hms_opt(hour, 0, 0),
+            Err(_) => parse_relative_time(time),
+        }
+    };
+
+    let (start, end) = arg
+        .split_once('-')
+        .ok_or_else(|| format!("expected working hours like 09:00-17:00, got `{}`", arg))?;
+
+    match (parse_time(start), parse_time(end)) {
+        (Some(start), Some(end)) if start < end => Ok((start, end)),
+        (Some(_), Some(_)) => Err(format!("working hours `{}` must end after they start", arg)),
+        _ => Err(format!(
+            "expected working hours like 09:00-17:00, got `{}`",
+            arg
+        )),
+    }
+}
+
+/// Whether the argument is a bare date in one of the built-in formats, without a time of day. The
+/// parsers fill in the current time for those.
+pub fn is_date_input(arg: &str, options: &ParseOptions) -> bool {
+    let (arg, _zone) = split_zone(arg.trim());
+
+    with_date_order(DATE_FORMATS, options.date_order)
+        .iter()
+        .filter(|format| !format.contains("%j") || has_ordinal_day(arg))
+        .any(|format| NaiveDate::parse_from_str(arg, format).is_ok())
+}
+
+/// Check whether the argument starts with a numeric date that could be read both day and month
+/// first, eg. `03/04/2020`. The error describes both readings, `date_order` first, and suggests
+/// the unambiguous ISO 8601 version of it.
+pub fn check_date_order(arg: &str, date_order: DateOrder) -> Result<(), ParseError> {
+    let date = split_zone(arg)
+        .0
+        .split([' ', 'T'])
+        .next()
+        .unwrap_or_default();
+    let separator = match date.chars().find(|c| !c.is_ascii_digit()) {
+        Some(separator) => separator,
+        None => return Ok(()),
+    };
+
+    let parts: Vec<&str> = date.split(separator).collect();
+    let (first, second, year) = match parts.as_slice() {
+        [first, second, year] if first.len() <= 2 && second.len() <= 2 && year.len() == 4 => {
+            match (first.parse(), second.parse(), year.parse()) {
+                (Ok(first), Ok(second), Ok(year)) => (first, second, year),
+                _ => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+
+    let month_first = NaiveDate::from_ymd_opt(year, first, second);
+    let day_first = NaiveDate::from_ymd_opt(year, second, first);
+    let (preferred, other) = match (date_order, day_first, month_first) {
+        (DateOrder::Dmy, Some(day_first), Some(month_first)) => (day_first, month_first),
+        (DateOrder::Mdy, Some(day_first), Some(month_first)) => (month_first, day_first),
+        _ => return Ok(()),
+    };
+    if preferred == other {
+        return Ok(());
+    }
+
+    let (format, orders) = match date_order {
+        DateOrder::Mdy => ("MM{}DD{}YYYY", ("MDY", "DMY")),
+        _ => ("DD{}MM{}YYYY", ("DMY", "MDY")),
+    };
+
+    Err(ParseError {
+        input: arg.to_string(),
+        closest_format: Some(format.replace("{}", &separator.to_string())),
+        position: 0,
+        length: date.len(),
+        reason: format!(
+            "date `{}` is ambiguous: {} ({}) or {} ({})",
+            date,
+            preferred.format("%-d %B %Y"),
+            orders.0,
+            other.format("%-d %B %Y"),
+            orders.1
+        ),
+        suggestion: Some(format!("{}{}", preferred, &arg[date.len()..])),
+    })
+}
+
+/// Tries to parse given argument as a complete RFC 3339 or RFC 2822 timestamp, eg. straight from
+/// logs or email headers. Both of these always contain their own offset.
+fn try_parse_rfc_formats(arg: &str) -> ParseResult<DateTime<Zone>> {
+    // For reference, full RFC 2822:  Tue, 1 Jul 2003 10:52:37 +0200
+    // and full RFC 3339 / ISO 8601:  1996-12-19T16:39:57.123-08:00
+    DateTime::parse_from_rfc3339(arg)
+        .or_else(|_err| DateTime::parse_from_rfc2822(arg))
+        .map(|datetime| datetime.with_timezone(&Zone::Fixed(*datetime.offset())))
+}
+
+/// Tries to parse given argument as a UNIX timestamp, eg. `@1591600334`, `1591600334000` or
+/// `@1591600334.25`.
+///
+/// The unit can be given explicitly with a `s`, `ms`, `us` or `ns` suffix, otherwise it's guessed
+/// from the magnitude so that any date between 1973 and 5138 works. Without the `@` prefix or a
+/// suffix, at least 9 digits are required so that random small numbers don't count as dates.
+fn try_parse_epoch(arg: &str, zone: &Zone) -> Option<DateTime<Zone>> {
+    let (arg, is_explicit) = match arg.strip_prefix('@') {
+        Some(arg) => (arg, true),
+        None => (arg, false),
+    };
+
+    let suffix_start = arg
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(arg.len());
+    let (number, suffix) = arg.split_at(suffix_start);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+    let whole: i64 = whole.parse().ok()?;
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let nanos_per_unit: i128 = match (suffix, whole.unsigned_abs()) {
+        ("s", _) => 1_000_000_000,
+        ("ms", _) => 1_000_000,
+        ("us", _) | ("µs", _) => 1_000,
+        ("ns", _) => 1,
+        ("", magnitude) if !is_explicit && magnitude < 100_000_000 => return None,
+        ("", 0..=99_999_999_999) => 1_000_000_000,
+        ("", 0..=99_999_999_999_999) => 1_000_000,
+        ("", 0..=99_999_999_999_999_999) => 1_000,
+        ("", _) => 1,
+        _ => return None,
+    };
+
+    // Fractions past nanosecond precision are simply dropped.
+    let fraction_digits = &fraction[..fraction.len().min(9)];
+    let fraction_nanos = match fraction_digits {
+        "" => 0,
+        _ => {
+            format!("{:0<9}", fraction_digits).parse::<i128>().ok()? * nanos_per_unit
+                / 1_000_000_000
+        }
+    };
+
+    let sign = if number.starts_with('-') { -1 } else { 1 };
+    let nanos = whole as i128 * nanos_per_unit + sign * fraction_nanos;
+    let seconds = nanos.div_euclid(1_000_000_000) as i64;
+    let subsec_nanos = nanos.rem_euclid(1_000_000_000) as u32;
+
+    zone.timestamp_opt(seconds, subsec_nanos).single()
+}
+
+/// Split an explicit timezone off the end of the argument, if there's any.
+///
+/// Supports IANA names and offsets separated with a space (`2020-06-08 10:00 Europe/Helsinki`)
+/// as well as `Z` and offsets attached straight to the time (`12:00Z`, `12:00+03:00`). Offsets
+/// are only accepted after a time, so that eg. `2018-12-24 -15:30` doesn't sneak through.
+fn split_zone(arg: &str) -> (&str, Option<Zone>) {
+    if let Some((rest, suffix)) = arg.trim_end().rsplit_once(' ') {
+        let is_named = suffix.starts_with(|c: char| c.is_ascii_alphabetic());
+        if is_named || rest.contains(':') {
+            if let Some(zone) = parse_zone(suffix) {
+                return (rest.trim_end(), Some(zone));
+            }
+        }
+    }
+
+    if let Some(index) = arg.rfind(['Z', '+', '-']) {
+        let (rest, suffix) = arg.split_at(index);
+        if rest.contains([':', 'T']) {
+            if let Some(zone) = parse_zone(suffix) {
+                return (rest, Some(zone));
+            }
+        }
+    }
+
+    (arg, None)
+}
+
+/// Supported time formats, tried in order.
+pub(crate) const TIME_FORMATS: &[&str] = &["%T%.f", "%R"];
+
+/// Supported date formats, tried in the order of (entirely subjective) "commonness".
+pub(crate) const DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%Y.%m.%d",
+    "%d-%m-%Y",
+    "%d/%m/%Y",
+    "%d.%m.%Y",
+    "%Y %B %d", // %B == July || Jul
+    "%d %B %Y",
+    // ISO 8601 week & ordinal dates
+    "%G-W%V-%u",
+    "%Y-%j",
+];
+
+/// Supported datetime formats, tried in order.
+pub(crate) const DATETIME_FORMATS: &[&str] = &[
+    // Month name
+    "%d %B %Y %H:%M:%S%.f",
+    "%Y %B %d %H:%M:%S%.f",
+    "%d %B %Y %H:%M",
+    "%Y %B %d %H:%M",
+    // Dashes
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%d-%m-%Y %H:%M:%S%.f",
+    "%d-%m-%Y %H:%M",
+    // Dots
+    "%Y.%m.%d %H:%M:%S%.f",
+    "%Y.%m.%d %H:%M",
+    "%d.%m.%Y %H:%M:%S%.f",
+    "%d.%m.%Y %H:%M",
+    // Slashes
+    "%Y/%m/%d %H:%M:%S%.f",
+    "%Y/%m/%d %H:%M",
+    "%d/%m/%Y %H:%M:%S%.f",
+    "%d/%m/%Y %H:%M",
+    // Dashes, dots & slashes, but with a T
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%d-%m-%YT%H:%M:%S%.f",
+    "%d-%m-%YT%H:%M",
+    "%Y.%m.%dT%H:%M:%S%.f",
+    "%Y.%m.%dT%H:%M",
+    "%d.%m.%YT%H:%M:%S%.f",
+    "%d.%m.%YT%H:%M",
+    "%Y/%m/%dT%H:%M:%S%.f",
+    "%Y/%m/%dT%H:%M",
+    "%d/%m/%YT%H:%M:%S%.f",
+    "%d/%m/%YT%H:%M",
+    // ISO 8601 week & ordinal dates, and the basic format without separators
+    "%G-W%V-%uT%H:%M:%S%.f",
+    "%G-W%V-%uT%H:%M",
+    "%Y-%jT%H:%M:%S%.f",
+    "%Y-%jT%H:%M",
+    "%Y%m%dT%H%M%S%.f",
+    "%Y%m%dT%H%M",
+    // TODO: All of the above, but with reverse date & time..?
+];
+
+/// Rearrange the day-first formats of the list to match the given date order.
+fn with_date_order(formats: &[&str], date_order: DateOrder) -> Vec<String> {
+    let day_first_dates = [
+        ("%d-%m-%Y", "%m-%d-%Y"),
+        ("%d/%m/%Y", "%m/%d/%Y"),
+        ("%d.%m.%Y", "%m.%d.%Y"),
+    ];
+
+    formats
+        .iter()
+        .filter_map(|format| {
+            let date = day_first_dates
+                .iter()
+                .find(|(day_first, _)| format.starts_with(day_first));
+
+            match (date_order, date) {
+                (DateOrder::Mdy, Some((day_first, month_first))) => {
+                    Some(format.replacen(day_first, month_first, 1))
+                }
+                (DateOrder::Ymd, Some(_)) => None,
+                _ => Some(format.to_string()),
+            }
         })
+        .collect()
+}
+
+/// Whether the input has a three digit day of year after the first dash, like ISO 8601 ordinal
+/// dates, eg. `2020-160`.
+fn has_ordinal_day(arg: &str) -> bool {
+    let rest = arg.split('-').nth(1).unwrap_or_default();
+    rest.chars().take_while(char::is_ascii_digit).count() == 3
+}
+
+/// Try each of the formats in order, returning the first successful result or the last error.
+fn try_formats<T, S, F>(formats: &[S], parse: F) -> ParseResult<T>
+where
+    S: AsRef<str>,
+    F: Fn(&str) -> ParseResult<T>,
+{
+    formats[1..]
+        .iter()
+        .fold(parse(formats[0].as_ref()), |result, format| {
+            result.or_else(|_err| parse(format.as_ref()))
+        })
+}
+
+/// Tries to parse given argument with user-defined strftime formats. These can describe a full
+/// datetime, with or without an offset, or just a date or a time like the built-in formats.
+fn try_parse_custom(arg: &str, formats: &[String], now: &DateTime<Zone>) -> Option<DateTime<Zone>> {
+    if formats.is_empty() {
+        return None;
+    }
+
+    try_formats(formats, |format| DateTime::parse_from_str(arg, format))
+        .map(|datetime| datetime.with_timezone(&Zone::Fixed(*datetime.offset())))
+        .or_else(|_err| try_parse_datetimes(arg, formats, &now.timezone()))
+        .ok()
+        .or_else(|| try_parse_dates(arg, formats, now))
+        .or_else(|| try_parse_times(arg, formats, now))
+}
+
+/// Tries to parse given argument through basic timestamp formats and create a locale-aware current
+/// datetime using the provided `now`. Returns `None` if the time doesn't exist on that day.
+fn try_parse_times<S: AsRef<str>>(
+    arg: &str,
+    formats: &[S],
+    now: &DateTime<Zone>,
+) -> Option<DateTime<Zone>> {
+    let time = try_formats(formats, |format| NaiveTime::parse_from_str(arg, format)).ok()?;
+
+    now.timezone()
+        .from_local_datetime(&now.naive_local().date().and_time(time))
+        .earliest()
+}
+
+/// Tries to parse given argument through multiple different date formats and create a locale-aware
+/// current datetime using the provided `now`. Returns `None` if the current time doesn't exist on
+/// that date.
+fn try_parse_dates<S: AsRef<str>>(
+    arg: &str,
+    formats: &[S],
+    now: &DateTime<Zone>,
+) -> Option<DateTime<Zone>> {
+    let date = try_formats(formats, |format| NaiveDate::parse_from_str(arg, format)).ok()?;
+
+    now.timezone()
+        .from_local_datetime(&date.and_time(now.time()))
+        .earliest()
 }
 
 /// Tries to parse given argument through multiple different datetime formats and create a
-/// locale-aware current datetime in the current system timezone.
-fn try_parse_datetimes(arg: &str) -> Result<DateTime<Local>, ParseError> {
-    // For reference, full RFC 2822:  1 Jul 2003 10:52:37 +0200
-    // and full RFC 3339 / ISO 8601:  1996-12-19T16:39:57-08:00
-
-    // TODO: Figure out if we could somehow "build" all the allowed formats a bit more nicely.
-    Local
-        // Month name
-        .datetime_from_str(&arg, "%d %B %Y %H:%M:%S")
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y %B %d %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d %B %Y %H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y %B %d %H:%M"))
-        // Dashes
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y-%m-%d %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y-%m-%d %H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d-%m-%Y %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d-%m-%Y %H:%M"))
-        // Dots
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y.%m.%d %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y.%m.%d %H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d.%m.%Y %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d.%m.%Y %H:%M"))
-        // Slashes
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y/%m/%d %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y/%m/%d %H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d/%m/%Y %H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d/%m/%Y %H:%M"))
-        // Dashes, dots & slashes, but with a T
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y-%m-%dT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y-%m-%dT%H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d-%m-%YT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d-%m-%YT%H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y.%m.%dT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y.%m.%dT%H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d.%m.%YT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d.%m.%YT%H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y/%m/%dT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%Y/%m/%dT%H:%M"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d/%m/%YT%H:%M:%S"))
-        .or_else(|_err| Local.datetime_from_str(&arg, "%d/%m/%YT%H:%M"))
+/// locale-aware datetime in the given timezone.
+fn try_parse_datetimes<S: AsRef<str>>(
+    arg: &str,
+    formats: &[S],
+    zone: &Zone,
+) -> ParseResult<DateTime<Zone>> {
+    try_formats(formats, |format| zone.datetime_from_str(arg, format))
+}
 
-    // TODO: All of the above, but with reverse date & time..?
+/// Tries to parse given argument as a relative expression, eg. `yesterday`, `tomorrow 9:00`,
+/// `next friday 17:00`, `noon`, `3 days ago` or `2 weeks from now`.
+///
+/// Like with dates, a missing time is filled out from `now`.
+fn try_parse_relative(arg: &str, now: &DateTime<Zone>) -> Option<DateTime<Zone>> {
+    let arg = arg.to_lowercase();
+    let words: Vec<&str> = arg.split_whitespace().collect();
+
+    match words.as_slice() {
+        ["now"] => Some(*now),
+        [amount, unit, "ago"] => shift_datetime(*now, -parse_amount(amount)?, parse_unit(unit)?),
+        [amount, unit, "from", "now"] | ["in", amount, unit] => {
+            shift_datetime(*now, parse_amount(amount)?, parse_unit(unit)?)
+        }
+        _ => {
+            let (date, rest) = parse_relative_date(&words, now)?;
+            let time = match rest {
+                [] => now.time(),
+                [time] => parse_relative_time(time)?,
+                _ => return None,
+            };
+
+            now.timezone()
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+        }
+    }
+}
+
+/// Parse the day part of a relative expression, returning the rest of the words untouched.
+fn parse_relative_date<'a>(
+    words: &'a [&'a str],
+    now: &DateTime<Zone>,
+) -> Option<(NaiveDate, &'a [&'a str])> {
+    let today = now.naive_local().date();
+    let days_from_monday = today.weekday().num_days_from_monday() as i64;
+
+    match words {
+        ["today", rest @ ..] => Some((today, rest)),
+        ["yesterday", rest @ ..] => Some((today.pred(), rest)),
+        ["tomorrow", rest @ ..] => Some((today.succ(), rest)),
+        // The closest matching weekday, but never today
+        ["last", weekday, rest @ ..] => {
+            let target = weekday.parse::<Weekday>().ok()?.num_days_from_monday() as i64;
+            let days_back = (days_from_monday - target + 6) % 7 + 1;
+            Some((today - Duration::days(days_back), rest))
+        }
+        ["next", weekday, rest @ ..] => {
+            let target = weekday.parse::<Weekday>().ok()?.num_days_from_monday() as i64;
+            let days_forward = (target - days_from_monday + 6) % 7 + 1;
+            Some((today + Duration::days(days_forward), rest))
+        }
+        // Only a time of day, eg. `noon`
+        [_] => Some((today, words)),
+        _ => None,
+    }
+}
+
+/// Parse the time part of a relative expression, either a named time or a basic timestamp.
+fn parse_relative_time(arg: &str) -> Option<NaiveTime> {
+    match arg {
+        "noon" => Some(NaiveTime::from_hms(12, 0, 0)),
+        "midnight" => Some(NaiveTime::from_hms(0, 0, 0)),
+        _ => NaiveTime::parse_from_str(arg, "%T%.f")
+            .or_else(|_err| NaiveTime::parse_from_str(arg, "%R"))
+            .ok(),
+    }
+}
+
+fn parse_amount(arg: &str) -> Option<i64> {
+    match arg {
+        "a" | "an" => Some(1),
+        _ => arg.parse().ok().filter(|amount: &i64| *amount >= 0),
+    }
+}
+
+/// Parse the unit of a relative expression or a duration, in either singular, plural or the
+/// usual abbreviations.
+fn parse_unit(arg: &str) -> Option<Filter> {
+    match arg {
+        "y" | "yr" | "yrs" | "year" | "years" => Some(Filter::Years),
+        "mo" | "mon" | "mons" | "month" | "months" => Some(Filter::Months),
+        "w" | "wk" | "wks" | "week" | "weeks" => Some(Filter::Weeks),
+        "d" | "day" | "days" => Some(Filter::Days),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(Filter::Hours),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Filter::Minutes),
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(Filter::Seconds),
+        _ => None,
+    }
 }
@@ -0,0 +1,22 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+/// Location of the data files, `since` in `$XDG_DATA_HOME` or `~/.local/share`.
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("since"))
+}
+
+/// Replace the file with the contents in one go, creating the directory if needed, so that a
+/// concurrent `since` never sees it half-written.
+pub(crate) fn replace_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
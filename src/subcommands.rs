@@ -1,7 +1,34 @@
 // While using enum-strings is over-verbose compared to simple const strings,
 // we'll consider this a fun excercise if nothing else.
 
+/// Subcommand for counting down instead, also used when invoked through an `until` symlink.
+pub const UNTIL: &str = "until";
+
+/// Subcommands for moving a datetime by a duration, instead of calculating a difference.
+pub const ADD: &str = "add";
+pub const SUB: &str = "sub";
+
+/// Subcommands for saving named datetimes and managing them.
+pub const MARK: &str = "mark";
+pub const MARKS: &str = "marks";
+
+/// Subcommands for the stopwatches kept on disk.
+pub const START: &str = "start";
+pub const STOP: &str = "stop";
+pub const LAP: &str = "lap";
+pub const PAUSE: &str = "pause";
+pub const RESUME: &str = "resume";
+
+/// Short forms of the filtering subcommands that stopped being unique prefixes when other
+/// subcommands were added, kept as hidden subcommands of their own so they still work.
+pub const ABBREVIATIONS: [(&str, Filter); 3] = [
+    ("mi", Filter::Minutes),
+    ("s", Filter::Seconds),
+    ("w", Filter::Weeks),
+];
+
 /// All supported subcommand branches
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Filter {
     Years,
     Months,
@@ -10,6 +37,13 @@ pub enum Filter {
     Hours,
     Minutes,
     Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+    Full,
+    Clock,
+    Workdays,
+    Workhours,
     None,
 }
 
@@ -24,11 +58,19 @@ impl Filter {
             Filter::Hours => "hours",
             Filter::Minutes => "minutes",
             Filter::Seconds => "seconds",
+            Filter::Milliseconds => "milliseconds",
+            Filter::Microseconds => "microseconds",
+            Filter::Nanoseconds => "nanoseconds",
+            Filter::Full => "full",
+            Filter::Clock => "clock",
+            Filter::Workdays => "workdays",
+            Filter::Workhours => "workhours",
             Filter::None => "NOT_SUBCMD",
         }
     }
 
-    // ...and back to enum from Clap.
+    // ...and back to enum from Clap. Unlike `FromStr`, this can't fail.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Filter {
         match s {
             "years" => Filter::Years,
@@ -38,7 +80,17 @@ impl Filter {
             "hours" => Filter::Hours,
             "minutes" => Filter::Minutes,
             "seconds" => Filter::Seconds,
-            _ => Filter::None,
+            "milliseconds" => Filter::Milliseconds,
+            "microseconds" => Filter::Microseconds,
+            "nanoseconds" => Filter::Nanoseconds,
+            "full" => Filter::Full,
+            "clock" => Filter::Clock,
+            "workdays" => Filter::Workdays,
+            "workhours" => Filter::Workhours,
+            _ => ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| *abbreviation == s)
+                .map_or(Filter::None, |(_, filter)| *filter),
         }
     }
 }
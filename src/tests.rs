@@ -1,25 +1,44 @@
 // Import with a shorthand for readability.
+use crate::bookmarks::{parse_bookmarks, Bookmarks};
+use crate::calculators::{
+    add_breakdown, calculate_breakdown, calculate_month_diff, calculate_units, calculate_year_diff,
+    until_next_tick, Breakdown, Difference, WorkCalendar,
+};
+use crate::config::{parse_config, parse_holidays, Config};
+use crate::formatters::{
+    get_iso_output, get_json_error_output, get_json_output, get_output, get_template_output,
+    parse_template, OutputOptions,
+};
 use crate::parsers::try_parse_all_formats as parse;
-use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+use crate::parsers::try_parse_range as parse_range;
+use crate::parsers::try_parse_with_options as parse_with;
+use crate::parsers::{
+    check_date_order, is_date_input, try_parse_duration, try_parse_intervals, try_parse_work_hours,
+    DateOrder, ParseOptions,
+};
+use crate::subcommands::Filter;
+use crate::timers::{parse_timers, Timer, Timers};
+use crate::zones::Zone;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
 
 //
 // A couple of shorthands, implemented with the same basic logic as the parsers:
 // fill out any missing date/time data with the value of `now`.
 //
-fn dt(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> DateTime<Local> {
-    Local.ymd(y, m, d).and_hms(h, min, s)
+fn dt(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> DateTime<Zone> {
+    Zone::Local.ymd(y, m, d).and_hms(h, min, s)
 }
 
-fn local_time(h: u32, m: u32, s: u32, now: DateTime<Local>) -> DateTime<Local> {
-    Local
+fn local_time(h: u32, m: u32, s: u32, now: DateTime<Zone>) -> DateTime<Zone> {
+    Zone::Local
         .ymd(now.year(), now.month(), now.day())
         .and_hms(h, m, s)
 }
 
-fn local_date(y: i32, m: u32, d: u32, now: DateTime<Local>) -> DateTime<Local> {
-    Local
+fn local_date(y: i32, m: u32, d: u32, now: DateTime<Zone>) -> DateTime<Zone> {
+    Zone::Local
         .ymd(y, m, d)
-        .and_hms(now.hour(), now.minute(), now.second())
+        .and_hms_nano(now.hour(), now.minute(), now.second(), now.nanosecond())
 }
 
 //
@@ -33,15 +52,19 @@ fn local_date(y: i32, m: u32, d: u32, now: DateTime<Local>) -> DateTime<Local> {
 //
 #[test]
 fn test_valid_time_formats_return_correct_datetimes() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     assert_eq!(parse("15:00", now).unwrap(), local_time(15, 0, 0, now));
     assert_eq!(parse("15:00:34", now).unwrap(), local_time(15, 0, 34, now));
+    assert_eq!(
+        parse("15:00:34.250", now).unwrap(),
+        local_time(15, 0, 34, now) + chrono::Duration::milliseconds(250)
+    );
 }
 
 #[test]
 fn test_invalid_time_formats_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     assert!(parse("15.00", now).is_err());
     assert!(parse("15:00.0", now).is_err());
@@ -51,7 +74,7 @@ fn test_invalid_time_formats_return_errors() {
 
 #[test]
 fn test_invalid_time_values_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     assert!(parse("f0:f0", now).is_err());
     assert!(parse("24:00", now).is_err());
@@ -66,7 +89,7 @@ fn test_invalid_time_values_return_errors() {
 //
 #[test]
 fn test_valid_date_formats_return_correct_datetimes() {
-    let now = Local::now();
+    let now = Zone::Local.now();
     let christmas = local_date(2018, 12, 24, now);
 
     assert_eq!(parse("2018-12-24", now).unwrap(), christmas);
@@ -87,7 +110,7 @@ fn test_valid_date_formats_return_correct_datetimes() {
 
 #[test]
 fn test_invalid_date_formats_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     // Different separators
     assert!(parse("2018|12|24", now).is_err());
@@ -104,7 +127,7 @@ fn test_invalid_date_formats_return_errors() {
 
 #[test]
 fn test_invalid_date_values_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     assert!(parse("f0.12.2018", now).is_err());
     assert!(parse("-24.12.2018", now).is_err());
@@ -116,12 +139,79 @@ fn test_invalid_date_values_return_errors() {
     assert!(parse("24.12.10000", now).is_err());
 }
 
+#[test]
+fn test_date_order_decides_how_numeric_dates_are_read() {
+    let now = Zone::Local.now();
+    let christmas = local_date(2018, 12, 24, now);
+    let options = |date_order, strict| ParseOptions {
+        date_order,
+        strict,
+        ..ParseOptions::default()
+    };
+
+    let us = options(DateOrder::Mdy, false);
+    assert_eq!(parse_with("12/24/2018", now, &us).unwrap(), christmas);
+    assert_eq!(parse_with("12.24.2018", now, &us).unwrap(), christmas);
+    assert_eq!(
+        parse_with("12-24-2018 10:00", now, &us).unwrap(),
+        dt(2018, 12, 24, 10, 0, 0)
+    );
+    assert!(parse_with("24/12/2018", now, &us).is_err());
+    // Year first and month names don't depend on the order
+    assert_eq!(parse_with("2018-12-24", now, &us).unwrap(), christmas);
+    assert_eq!(parse_with("24 Dec 2018", now, &us).unwrap(), christmas);
+
+    let iso = options(DateOrder::Ymd, false);
+    assert_eq!(parse_with("2018/12/24", now, &iso).unwrap(), christmas);
+    assert!(parse_with("24/12/2018", now, &iso).is_err());
+    assert!(parse_with("12/24/2018", now, &iso).is_err());
+
+    // Ambiguous dates go with the preference, unless strict
+    assert_eq!(
+        parse("03/04/2020", now).unwrap(),
+        local_date(2020, 4, 3, now)
+    );
+    assert_eq!(
+        parse_with("03/04/2020", now, &us).unwrap(),
+        local_date(2020, 3, 4, now)
+    );
+    assert!(parse_with("03/04/2020", now, &options(DateOrder::Dmy, true)).is_err());
+    assert!(parse_with("04/04/2020", now, &options(DateOrder::Dmy, true)).is_ok());
+    assert!(parse_with("24/12/2018", now, &options(DateOrder::Dmy, true)).is_ok());
+}
+
+#[test]
+fn test_ambiguous_dates_are_detected() {
+    let err = check_date_order("03/04/2020 10:00", DateOrder::Dmy).unwrap_err();
+    assert_eq!(
+        err.reason(),
+        "date `03/04/2020` is ambiguous: 3 April 2020 (DMY) or 4 March 2020 (MDY)"
+    );
+    assert_eq!(err.span(), (0, 10));
+    assert_eq!(err.suggestion(), Some("2020-04-03 10:00"));
+
+    let err = check_date_order("3.4.2020", DateOrder::Mdy).unwrap_err();
+    assert_eq!(err.closest_format(), Some("MM.DD.YYYY"));
+    assert_eq!(err.suggestion(), Some("2020-03-04"));
+
+    assert!(check_date_order("04/04/2020", DateOrder::Dmy).is_ok());
+    assert!(check_date_order("13/04/2020", DateOrder::Dmy).is_ok());
+    assert!(check_date_order("2020/04/03", DateOrder::Dmy).is_ok());
+    assert!(check_date_order("03/04/2020", DateOrder::Ymd).is_ok());
+    // Multi-byte separators are fine too
+    assert!(check_date_order("2020年6月8日", DateOrder::Dmy).is_ok());
+    assert!(check_date_order("10€", DateOrder::Dmy).is_ok());
+    let err = check_date_order("03€04€2020", DateOrder::Dmy).unwrap_err();
+    assert_eq!(err.closest_format(), Some("DD€MM€YYYY"));
+    assert!(parse("é 3", Zone::Local.now()).is_err());
+}
+
 //
 // DATETIMES
 //
 #[test]
 fn test_valid_datetime_formats_return_correct_datetimes() {
-    let now = Local::now();
+    let now = Zone::Local.now();
     let christmas = dt(2018, 12, 24, 15, 30, 45);
     let secondless = dt(2018, 12, 24, 15, 30, 0);
 
@@ -158,6 +248,15 @@ fn test_valid_datetime_formats_return_correct_datetimes() {
     assert_eq!(parse("2018/12/24T15:30", now).unwrap(), secondless);
     assert_eq!(parse("24/12/2018T15:30:45", now).unwrap(), christmas);
     assert_eq!(parse("24/12/2018T15:30", now).unwrap(), secondless);
+    // Fractional seconds
+    assert_eq!(
+        parse("24.12.2018 15:30:45.123456", now).unwrap(),
+        christmas + chrono::Duration::microseconds(123_456)
+    );
+    assert_eq!(
+        parse("2018 Dec 24 15:30:45.5", now).unwrap(),
+        christmas + chrono::Duration::milliseconds(500)
+    );
 
     // Limits
     assert_eq!(
@@ -170,9 +269,62 @@ fn test_valid_datetime_formats_return_correct_datetimes() {
     );
 }
 
+#[test]
+fn test_valid_iso_8601_and_rfc_formats_return_correct_datetimes() {
+    let now = Zone::Local.now();
+    let utc = Utc.ymd(2020, 6, 8).and_hms(10, 30, 45);
+    let utc_millis = Utc.ymd(2020, 6, 8).and_hms_milli(10, 30, 45, 250);
+
+    // RFC 3339
+    assert_eq!(parse("2020-06-08T10:30:45Z", now).unwrap(), utc);
+    assert_eq!(parse("2020-06-08T13:30:45+03:00", now).unwrap(), utc);
+    assert_eq!(parse("2020-06-08t10:30:45z", now).unwrap(), utc);
+    assert_eq!(parse("2020-06-08T10:30:45.25Z", now).unwrap(), utc_millis);
+    assert_eq!(
+        parse("2020-06-08T13:30:45.250+0300", now).unwrap(),
+        utc_millis
+    );
+    assert_eq!(
+        parse("2020-06-08 10:30:45.25 UTC", now).unwrap(),
+        utc_millis
+    );
+
+    // RFC 2822
+    assert_eq!(parse("Mon, 8 Jun 2020 13:30:45 +0300", now).unwrap(), utc);
+    assert_eq!(parse("Mon, 08 Jun 2020 10:30:45 GMT", now).unwrap(), utc);
+
+    // ISO 8601 week dates, ordinal dates and the basic format
+    assert_eq!(
+        parse("2020-W24-1", now).unwrap(),
+        local_date(2020, 6, 8, now)
+    );
+    assert_eq!(parse("2020-160", now).unwrap(), local_date(2020, 6, 8, now));
+    assert_eq!(parse("2020-W24-1T10:30:45Z", now).unwrap(), utc);
+    assert_eq!(parse("2020-160T10:30:45Z", now).unwrap(), utc);
+    assert_eq!(parse("20200608T103045Z", now).unwrap(), utc);
+    assert_eq!(
+        parse("2020-W24-1T10:30", now).unwrap(),
+        dt(2020, 6, 8, 10, 30, 0)
+    );
+}
+
+#[test]
+fn test_invalid_iso_8601_values_return_errors() {
+    let now = Zone::Local.now();
+
+    assert!(parse("2020-W54-1", now).is_err());
+    assert!(parse("2020-W24-8", now).is_err());
+    assert!(parse("2019-366", now).is_err());
+    // Year and month, not the 6th day of the year
+    assert!(parse("2020-06", now).is_err());
+    assert!(parse("2020-6", now).is_err());
+    assert!(parse("2020-06T10:30", now).is_err());
+    assert!(parse("2020-06-08T10:30:45.Z", now).is_err());
+}
+
 #[test]
 fn test_invalid_datetime_formats_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     // Only specific separators allowed
     assert!(parse("2018-12-24-15:00:00", now).is_err());
@@ -184,7 +336,7 @@ fn test_invalid_datetime_formats_return_errors() {
 
 #[test]
 fn test_invalid_datetime_values_return_errors() {
-    let now = Local::now();
+    let now = Zone::Local.now();
 
     // Invalid time values
     assert!(parse("2018-12-24 f0:f0", now).is_err());
@@ -204,3 +356,1271 @@ fn test_invalid_datetime_values_return_errors() {
     assert!(parse("-1-24-24 15:00:00", now).is_err());
     assert!(parse("10000-24-24 15:00:00", now).is_err());
 }
+
+//
+// UNIX TIMESTAMPS
+//
+#[test]
+fn test_valid_epochs_return_correct_datetimes() {
+    let now = Zone::Local.now();
+    let epoch = Utc.ymd(2020, 6, 8).and_hms(7, 12, 14);
+    let epoch_millis = Utc.ymd(2020, 6, 8).and_hms_milli(7, 12, 14, 250);
+
+    // Magnitude based guessing
+    assert_eq!(parse("@1591600334", now).unwrap(), epoch);
+    assert_eq!(parse("1591600334", now).unwrap(), epoch);
+    assert_eq!(parse("1591600334250", now).unwrap(), epoch_millis);
+    assert_eq!(parse("1591600334250000", now).unwrap(), epoch_millis);
+    assert_eq!(parse("1591600334250000000", now).unwrap(), epoch_millis);
+    assert_eq!(parse("@1591600334.25", now).unwrap(), epoch_millis);
+    assert_eq!(
+        parse("@0", now).unwrap(),
+        Utc.ymd(1970, 1, 1).and_hms(0, 0, 0)
+    );
+    assert_eq!(
+        parse("@-86400", now).unwrap(),
+        Utc.ymd(1969, 12, 31).and_hms(0, 0, 0)
+    );
+
+    // Explicit units
+    assert_eq!(parse("@1591600334s", now).unwrap(), epoch);
+    assert_eq!(parse("@1591600334250ms", now).unwrap(), epoch_millis);
+    assert_eq!(parse("1591600334250000us", now).unwrap(), epoch_millis);
+    assert_eq!(parse("1591600334250000000ns", now).unwrap(), epoch_millis);
+    assert_eq!(parse("@1591600334.25s", now).unwrap(), epoch_millis);
+    assert_eq!(
+        parse("@1591600334250.5ms", now).unwrap(),
+        epoch_millis + chrono::Duration::microseconds(500)
+    );
+    assert_eq!(
+        parse("@1000ms", now).unwrap(),
+        Utc.ymd(1970, 1, 1).and_hms(0, 0, 1)
+    );
+}
+
+#[test]
+fn test_invalid_epochs_return_errors() {
+    let now = Zone::Local.now();
+
+    // Too short without an explicit prefix or unit
+    assert!(parse("2020", now).is_err());
+    assert!(parse("12345678", now).is_err());
+
+    assert!(parse("@", now).is_err());
+    assert!(parse("@abc", now).is_err());
+    assert!(parse("@1591600334h", now).is_err());
+    assert!(parse("@1591600334.2.5", now).is_err());
+    assert!(parse("@1591600334.-5", now).is_err());
+    assert!(parse("@99999999999999999999", now).is_err());
+}
+
+//
+// RELATIVE EXPRESSIONS
+//
+#[test]
+fn test_valid_relative_expressions_return_correct_datetimes() {
+    // Monday
+    let now = dt(2020, 6, 8, 10, 12, 14);
+
+    assert_eq!(parse("now", now).unwrap(), now);
+    assert_eq!(parse("today", now).unwrap(), now);
+    assert_eq!(parse("yesterday", now).unwrap(), dt(2020, 6, 7, 10, 12, 14));
+    assert_eq!(
+        parse("Tomorrow 9:00", now).unwrap(),
+        dt(2020, 6, 9, 9, 0, 0)
+    );
+    assert_eq!(parse("noon", now).unwrap(), dt(2020, 6, 8, 12, 0, 0));
+    assert_eq!(parse("midnight", now).unwrap(), dt(2020, 6, 8, 0, 0, 0));
+    assert_eq!(
+        parse("yesterday noon", now).unwrap(),
+        dt(2020, 6, 7, 12, 0, 0)
+    );
+
+    // Weekdays are never today
+    assert_eq!(
+        parse("last monday", now).unwrap(),
+        dt(2020, 6, 1, 10, 12, 14)
+    );
+    assert_eq!(
+        parse("next monday", now).unwrap(),
+        dt(2020, 6, 15, 10, 12, 14)
+    );
+    assert_eq!(parse("last sun", now).unwrap(), dt(2020, 6, 7, 10, 12, 14));
+    assert_eq!(
+        parse("next Friday 17:00", now).unwrap(),
+        dt(2020, 6, 12, 17, 0, 0)
+    );
+
+    assert_eq!(
+        parse("3 days ago", now).unwrap(),
+        dt(2020, 6, 5, 10, 12, 14)
+    );
+    assert_eq!(
+        parse("an hour ago", now).unwrap(),
+        dt(2020, 6, 8, 9, 12, 14)
+    );
+    assert_eq!(
+        parse("2 weeks from now", now).unwrap(),
+        dt(2020, 6, 22, 10, 12, 14)
+    );
+    assert_eq!(
+        parse("in 1 month", now).unwrap(),
+        dt(2020, 7, 8, 10, 12, 14)
+    );
+    assert_eq!(
+        parse("1 year ago", now).unwrap(),
+        dt(2019, 6, 8, 10, 12, 14)
+    );
+    assert_eq!(parse("2 hrs ago", now).unwrap(), dt(2020, 6, 8, 8, 12, 14));
+}
+
+#[test]
+fn test_invalid_relative_expressions_return_errors() {
+    let now = Zone::Local.now();
+
+    assert!(parse("last", now).is_err());
+    assert!(parse("next day", now).is_err());
+    assert!(parse("tomorrow 25:00", now).is_err());
+    assert!(parse("tomorrow at noon", now).is_err());
+    assert!(parse("-3 days ago", now).is_err());
+    assert!(parse("3 fortnights ago", now).is_err());
+    assert!(parse("3 days", now).is_err());
+    // Out of range, instead of overflowing
+    assert!(parse("99999999999 years ago", now).is_err());
+    assert!(parse("999999999999 days ago", now).is_err());
+    assert!(parse("in 99999999999999 hours", now).is_err());
+    assert!(parse("in 9223372036854775807 seconds", now).is_err());
+}
+
+#[test]
+fn test_ranges_are_split_into_from_and_to() {
+    let now = Zone::Local.now();
+    let options = ParseOptions::default();
+    let range = |line| parse_range(line, now, &options).unwrap();
+
+    assert_eq!(range("10:00"), (local_time(10, 0, 0, now), None));
+    assert_eq!(
+        range("  2020-06-08 10:00  "),
+        (dt(2020, 6, 8, 10, 0, 0), None)
+    );
+    assert_eq!(
+        range("10:00 12:30"),
+        (local_time(10, 0, 0, now), Some(local_time(12, 30, 0, now)))
+    );
+    assert_eq!(
+        range("2020-06-08 10:00 2020-06-09 12:30"),
+        (dt(2020, 6, 8, 10, 0, 0), Some(dt(2020, 6, 9, 12, 30, 0)))
+    );
+    assert_eq!(
+        range("24 Dec 2019\t3 days ago"),
+        (
+            local_date(2019, 12, 24, now),
+            Some(parse("3 days ago", now).unwrap())
+        )
+    );
+
+    assert!(parse_range("10:00 foo", now, &options).is_err());
+    assert!(parse_range("10:00\tfoo", now, &options).is_err());
+
+    let err = parse_range("24.13.2019", now, &options).unwrap_err();
+    assert_eq!(
+        get_json_error_output(&err),
+        r#"{"input":"24.13.2019","error":"month 13 is out of range (1-12) (closest format: DD.MM.YYYY)"}"#
+    );
+}
+
+//
+// DURATIONS
+//
+#[test]
+fn test_valid_durations_return_correct_breakdowns() {
+    let duration = |arg| try_parse_duration(arg).unwrap();
+
+    assert_eq!(
+        duration("3 weeks 2 days"),
+        Breakdown {
+            days: 23,
+            ..Breakdown::default()
+        }
+    );
+    assert_eq!(
+        duration("90d"),
+        Breakdown {
+            days: 90,
+            ..Breakdown::default()
+        }
+    );
+    assert_eq!(
+        duration("1h30m"),
+        Breakdown {
+            hours: 1,
+            minutes: 30,
+            ..Breakdown::default()
+        }
+    );
+    assert_eq!(
+        duration("a year and 2 months"),
+        Breakdown {
+            years: 1,
+            months: 2,
+            ..Breakdown::default()
+        }
+    );
+    assert_eq!(
+        duration("P1Y2M3DT4H5M6S"),
+        Breakdown {
+            years: 1,
+            months: 2,
+            days: 3,
+            hours: 4,
+            minutes: 5,
+            seconds: 6,
+        }
+    );
+    assert_eq!(
+        duration("P2W"),
+        Breakdown {
+            days: 14,
+            ..Breakdown::default()
+        }
+    );
+}
+
+#[test]
+fn test_invalid_durations_return_errors() {
+    assert!(try_parse_duration("").is_err());
+    assert!(try_parse_duration("3").is_err());
+    assert!(try_parse_duration("days").is_err());
+    assert!(try_parse_duration("-3 days").is_err());
+    assert!(try_parse_duration("P").is_err());
+    assert!(try_parse_duration("PT1Y").is_err());
+
+    let err = try_parse_duration("3 weks").unwrap_err();
+    assert_eq!(err.suggestion(), Some("3 weeks"));
+
+    let err = try_parse_duration("1d 2000000000000000000weeks").unwrap_err();
+    assert_eq!(err.reason(), "amount is too large");
+    assert_eq!(err.span(), (3, 22));
+
+    let err = try_parse_duration("P1DT20000000000000000000H").unwrap_err();
+    assert_eq!(err.reason(), "amount is too large");
+    assert_eq!(err.span(), (4, 24));
+
+    let err = try_parse_duration("P2000000000000000000W").unwrap_err();
+    assert_eq!(err.reason(), "amount is too large");
+    assert_eq!(err.span(), (1, 20));
+}
+
+#[test]
+fn test_durations_are_added_on_the_calendar() {
+    let duration = |arg| try_parse_duration(arg).unwrap();
+
+    assert_eq!(
+        add_breakdown(dt(2020, 6, 8, 10, 0, 0), &duration("3 weeks 2 days")),
+        Some(dt(2020, 7, 1, 10, 0, 0))
+    );
+    assert_eq!(
+        add_breakdown(dt(2020, 6, 8, 10, 0, 0), &-duration("90d")),
+        Some(dt(2020, 3, 10, 10, 0, 0))
+    );
+    assert_eq!(
+        add_breakdown(dt(2020, 6, 8, 23, 30, 0), &duration("1h")),
+        Some(dt(2020, 6, 9, 0, 30, 0))
+    );
+    // Month ends are clamped the same way as in the differences
+    assert_eq!(
+        add_breakdown(dt(2020, 1, 31, 10, 0, 0), &duration("1mo")),
+        Some(dt(2020, 2, 29, 10, 0, 0))
+    );
+    assert_eq!(
+        add_breakdown(dt(2020, 2, 29, 0, 0, 0), &-duration("1 year")),
+        Some(dt(2019, 2, 28, 0, 0, 0))
+    );
+    // Out of range, instead of overflowing
+    let now = dt(2020, 6, 8, 10, 0, 0);
+    assert_eq!(add_breakdown(now, &duration("999999999years")), None);
+    assert_eq!(add_breakdown(now, &-duration("99999999999999d")), None);
+    assert_eq!(add_breakdown(now, &duration("9999999999h")), None);
+    assert_eq!(add_breakdown(now, &duration("9223372036854775807s")), None);
+}
+
+#[test]
+fn test_only_bare_dates_are_date_inputs() {
+    let options = ParseOptions::default();
+
+    assert!(is_date_input("2020-06-08", &options));
+    assert!(is_date_input("8.6.2020", &options));
+    assert!(is_date_input("2020-160", &options));
+    assert!(!is_date_input("2020-06-08 12:00", &options));
+    assert!(!is_date_input("now", &options));
+    assert!(!is_date_input("12:00", &options));
+    assert!(!is_date_input("2020-06", &options));
+}
+
+#[test]
+fn test_timesheet_lines_are_split_into_intervals() {
+    let now = dt(2020, 6, 8, 18, 0, 0);
+    let options = ParseOptions::default();
+    let intervals = |line| try_parse_intervals(line, now, &options).unwrap();
+
+    assert_eq!(
+        intervals("09:00-12:30 13:15-17:45"),
+        vec![
+            (dt(2020, 6, 8, 9, 0, 0), dt(2020, 6, 8, 12, 30, 0)),
+            (dt(2020, 6, 8, 13, 15, 0), dt(2020, 6, 8, 17, 45, 0))
+        ]
+    );
+    assert_eq!(
+        intervals("2020-06-01T09:00-2020-06-01T10:15"),
+        vec![(dt(2020, 6, 1, 9, 0, 0), dt(2020, 6, 1, 10, 15, 0))]
+    );
+    // Past midnight
+    assert_eq!(
+        intervals("22:00-02:00"),
+        vec![(dt(2020, 6, 8, 22, 0, 0), dt(2020, 6, 9, 2, 0, 0))]
+    );
+    // Pairs work the same as everywhere else
+    assert_eq!(
+        intervals("2020-06-01 09:00 2020-06-01 10:15"),
+        vec![(dt(2020, 6, 1, 9, 0, 0), dt(2020, 6, 1, 10, 15, 0))]
+    );
+    assert_eq!(
+        intervals("09:00\t12:00"),
+        vec![(dt(2020, 6, 8, 9, 0, 0), dt(2020, 6, 8, 12, 0, 0))]
+    );
+
+    assert!(try_parse_intervals("09:00", now, &options).is_err());
+    assert!(try_parse_intervals("09:00-lunch", now, &options).is_err());
+    assert!(try_parse_intervals("09:00-12:00 13:00", now, &options).is_err());
+
+    // Only times roll over past midnight, backwards datetimes are errors.
+    let err = try_parse_intervals(
+        "09:00-10:00 2020-06-09T10:00-2020-06-05T10:00",
+        now,
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.reason(),
+        "range `2020-06-09T10:00-2020-06-05T10:00` ends before it starts"
+    );
+    assert_eq!(err.span(), (12, 45));
+}
+
+//
+// TIMEZONES
+//
+fn helsinki() -> Zone {
+    Zone::Named(chrono_tz::Europe::Helsinki)
+}
+
+#[test]
+fn test_explicit_timezones_are_converted_into_zone_of_now() {
+    let now = Utc
+        .ymd(2020, 6, 7)
+        .and_hms(22, 0, 0)
+        .with_timezone(&helsinki());
+    let utc = |d, h, min| Utc.ymd(2020, 6, d).and_hms(h, min, 0);
+
+    assert_eq!(
+        parse("2020-06-08 10:00 Europe/Helsinki", now).unwrap(),
+        utc(8, 7, 0)
+    );
+    assert_eq!(parse("2020-06-08 10:00 UTC", now).unwrap(), utc(8, 10, 0));
+    assert_eq!(parse("2020-06-08T10:00Z", now).unwrap(), utc(8, 10, 0));
+    assert_eq!(parse("2020-06-08T10:00+02:00", now).unwrap(), utc(8, 8, 0));
+    assert_eq!(
+        parse("2020-06-08 10:00 -0130", now).unwrap(),
+        utc(8, 11, 30)
+    );
+
+    // Naive inputs use the zone of `now`, which is already the next day in Helsinki
+    assert_eq!(parse("10:00", now).unwrap(), utc(8, 7, 0));
+    assert_eq!(parse("10:00Z", now).unwrap(), utc(7, 10, 0));
+    assert_eq!(parse("10:00 +01", now).unwrap(), utc(7, 9, 0));
+
+    assert_eq!(parse("10:00", now).unwrap().timezone(), helsinki());
+    assert_eq!(parse("10:00Z", now).unwrap().timezone(), helsinki());
+}
+
+#[test]
+fn test_invalid_timezones_return_errors() {
+    let now = Zone::Local.now();
+
+    assert!(parse("2020-06-08 10:00 Europe/Nowhere", now).is_err());
+    assert!(parse("2020-06-08 10:00+25:00", now).is_err());
+    assert!(parse("2020-06-08 10:00 +2", now).is_err());
+    // Offsets need a time before them
+    assert!(parse("2020-06-08 +02:00", now).is_err());
+}
+
+#[test]
+fn test_calendar_math_follows_daylight_saving_time() {
+    let zone = helsinki();
+    let winter = zone.ymd(2020, 3, 1).and_hms(12, 0, 0);
+    let summer = zone.ymd(2020, 4, 1).and_hms(12, 0, 0);
+
+    assert_eq!(calculate_month_diff(winter, summer), 1);
+    assert_eq!(
+        calculate_breakdown(winter, summer),
+        Breakdown {
+            months: 1,
+            ..Breakdown::default()
+        }
+    );
+}
+
+#[test]
+fn test_times_skipped_by_daylight_saving_time_return_errors() {
+    // Clocks go from 03:00 to 04:00 on that day in Helsinki.
+    let zone = helsinki();
+    let now = zone.ymd(2020, 3, 29).and_hms(12, 0, 0);
+
+    let err = parse("2020-03-29 03:30", now).unwrap_err();
+    assert_eq!(err.reason(), "the time doesn't exist in the timezone");
+    assert!(parse("03:30", now).is_err());
+    assert_eq!(
+        parse("04:30", now).unwrap(),
+        zone.ymd(2020, 3, 29).and_hms(4, 30, 0)
+    );
+    // Dates get the time of `now`, which may not exist on that date either
+    let now = zone.ymd(2020, 3, 1).and_hms(3, 30, 0);
+    assert!(parse("2020-03-29", now).is_err());
+}
+
+//
+// PARSE ERRORS
+//
+#[test]
+fn test_parse_errors_point_out_invalid_values() {
+    let now = Zone::Local.now();
+
+    let err = parse("24.13.2019", now).unwrap_err();
+    assert_eq!(err.input(), "24.13.2019");
+    assert_eq!(err.closest_format(), Some("DD.MM.YYYY"));
+    assert_eq!(err.reason(), "month 13 is out of range (1-12)");
+    assert_eq!(err.span(), (3, 5));
+    assert_eq!(err.suggestion(), None);
+
+    let err = parse("2019-02-29 10:00", now).unwrap_err();
+    assert_eq!(err.closest_format(), Some("YYYY-MM-DD HH:MM"));
+    assert_eq!(err.reason(), "February 2019 only has 28 days");
+    assert_eq!(err.span(), (8, 10));
+
+    let err = parse("15:60", now).unwrap_err();
+    assert_eq!(err.closest_format(), Some("HH:MM"));
+    assert_eq!(err.span(), (3, 5));
+
+    let err = parse("15:00:61", now).unwrap_err();
+    assert_eq!(err.reason(), "second 61 is out of range (0-60)");
+    assert_eq!(err.span(), (6, 8));
+
+    let err = parse("2019-01-0x", now).unwrap_err();
+    assert_eq!(err.closest_format(), Some("YYYY-MM-DD"));
+    assert_eq!(err.span(), (9, 10));
+}
+
+#[test]
+fn test_parse_errors_suggest_likely_corrections() {
+    let now = Zone::Local.now();
+
+    let suggest = |arg| parse(arg, now).unwrap_err().suggestion().map(String::from);
+
+    // Day and month the wrong way around
+    assert_eq!(suggest("12.24.2019"), Some(String::from("24.12.2019")));
+    assert_eq!(
+        suggest("2019-13-01 10:00 Europe/Helsinki"),
+        Some(String::from("2019-01-13 10:00 Europe/Helsinki"))
+    );
+    // Typos
+    assert_eq!(
+        suggest("24 Decmber 2019"),
+        Some(String::from("24 December 2019"))
+    );
+    assert_eq!(suggest("tomorow"), Some(String::from("tomorrow")));
+    assert_eq!(suggest("3 dayz ago"), Some(String::from("3 days ago")));
+    // Nothing close enough
+    assert_eq!(suggest("24.13.2019"), None);
+    assert_eq!(suggest("foo"), None);
+}
+
+//
+// CUSTOM FORMATS
+//
+#[test]
+fn test_custom_formats_are_tried_in_order() {
+    let now = Zone::Local.now();
+    let first = |formats: &[&str]| ParseOptions {
+        formats_first: formats.iter().map(|format| format.to_string()).collect(),
+        ..ParseOptions::default()
+    };
+    let last = |formats: &[&str]| ParseOptions {
+        formats_last: formats.iter().map(|format| format.to_string()).collect(),
+        ..ParseOptions::default()
+    };
+
+    // Datetimes, dates and times
+    let compact = first(&["%d%m%Y-%H%M"]);
+    assert_eq!(
+        parse_with("24122018-1015", now, &compact).unwrap(),
+        dt(2018, 12, 24, 10, 15, 0)
+    );
+    assert!(parse("24122018-1015", now).is_err());
+    assert_eq!(
+        parse_with("IMG_20181224.jpg", now, &last(&["IMG_%Y%m%d.jpg"])).unwrap(),
+        local_date(2018, 12, 24, now)
+    );
+    assert_eq!(
+        parse_with("10h15", now, &last(&["%Hh%M"])).unwrap(),
+        local_time(10, 15, 0, now)
+    );
+    // With an offset
+    assert_eq!(
+        parse_with(
+            "24/12/2018 10:15 +0000",
+            now,
+            &first(&["%d/%m/%Y %H:%M %z"])
+        )
+        .unwrap(),
+        Utc.ymd(2018, 12, 24).and_hms(10, 15, 0)
+    );
+
+    // Formats tried first win over the built-in ones, the ones tried last don't.
+    let month_first = ["%m.%d.%Y"];
+    assert_eq!(
+        parse_with("03.04.2020", now, &first(&month_first)).unwrap(),
+        local_date(2020, 3, 4, now)
+    );
+    assert_eq!(
+        parse_with("03.04.2020", now, &last(&month_first)).unwrap(),
+        local_date(2020, 4, 3, now)
+    );
+
+    // And they're part of the diagnostics.
+    let err = parse_with("24122018-10:15", now, &compact).unwrap_err();
+    assert_eq!(err.closest_format(), Some("DDMMYYYY-HHMM"));
+}
+
+#[test]
+fn test_config_file_is_parsed() {
+    let config = parse_config(
+        r#"
+        # Camera files
+        input_formats = [
+            "IMG_%Y%m%d_%H%M%S.jpg", # Phone
+            '%d%m%Y-%H%M',
+        ]
+        input_formats_position = "before"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.input_formats,
+        vec!["IMG_%Y%m%d_%H%M%S.jpg", "%d%m%Y-%H%M"]
+    );
+    assert!(config.input_formats_first);
+
+    let config = parse_config(r##"input_formats = ["#%Y \"%m\"", "%d,%m"]"##).unwrap();
+    assert_eq!(config.input_formats, vec!["#%Y \"%m\"", "%d,%m"]);
+    assert!(!config.input_formats_first);
+
+    assert_eq!(parse_config("").unwrap(), Config::default());
+    assert!(parse_config("input_formats = \"%Y\"").is_err());
+    assert!(parse_config("input_formats = [\"%Y\"").is_err());
+    assert!(parse_config("input_formats_position = \"sometimes\"").is_err());
+    assert!(parse_config("colour = \"blue\"").is_err());
+}
+
+//
+// BOOKMARKS
+//
+#[test]
+fn test_bookmarks_are_resolved_before_other_formats() {
+    let now = dt(2020, 6, 8, 10, 12, 14);
+    let mut bookmarks = Bookmarks::default();
+    bookmarks.insert("deploy", dt(2020, 6, 1, 9, 30, 0));
+    bookmarks.insert("now", dt(2020, 1, 1, 0, 0, 0));
+    let options = ParseOptions {
+        bookmarks,
+        ..ParseOptions::default()
+    };
+
+    assert_eq!(
+        parse_with("deploy", now, &options).unwrap(),
+        dt(2020, 6, 1, 9, 30, 0)
+    );
+    assert_eq!(
+        parse_with(" now ", now, &options).unwrap(),
+        dt(2020, 1, 1, 0, 0, 0)
+    );
+    assert_eq!(
+        parse_range("deploy\t10:00", now, &options).unwrap(),
+        (dt(2020, 6, 1, 9, 30, 0), Some(dt(2020, 6, 8, 10, 0, 0)))
+    );
+    assert_eq!(parse("now", now).unwrap(), now);
+
+    let err = parse_with("deplyo", now, &options).unwrap_err();
+    assert_eq!(err.reason(), "unknown bookmark `deplyo`");
+    assert_eq!(err.suggestion(), Some("deploy"));
+}
+
+#[test]
+fn test_bookmarks_file_is_parsed_and_written() {
+    let text = "deploy\t2020-06-08T10:00:00+03:00\n\nrelease\t2020-06-09T12:30:00.5+00:00\n";
+    let mut bookmarks = parse_bookmarks(text).unwrap();
+    let utc = Zone::Fixed(chrono::FixedOffset::east(0));
+
+    assert_eq!(
+        bookmarks.get("deploy", &utc),
+        Some(utc.ymd(2020, 6, 8).and_hms(7, 0, 0))
+    );
+    assert_eq!(bookmarks.names().collect::<Vec<_>>(), ["deploy", "release"]);
+    assert_eq!(parse_bookmarks(&bookmarks.to_file()).unwrap(), bookmarks);
+
+    assert!(bookmarks.rename("deploy", "hotfix"));
+    assert!(!bookmarks.rename("deploy", "other"));
+    assert!(!bookmarks.rename("hotfix", "release"));
+    assert!(bookmarks.remove("release"));
+    assert!(!bookmarks.remove("release"));
+    assert_eq!(bookmarks.to_file(), "hotfix\t2020-06-08T10:00:00+03:00\n");
+
+    assert!(parse_bookmarks("deploy 2020-06-08T10:00:00Z").is_err());
+    assert!(parse_bookmarks("deploy\tyesterday").is_err());
+}
+
+//
+// TIMERS
+//
+#[test]
+fn test_timers_skip_the_time_spent_paused() {
+    let minutes = chrono::Duration::minutes;
+    let mut timer = Timer::start(dt(2020, 6, 8, 10, 0, 0));
+
+    assert!(timer.is_running());
+    assert_eq!(timer.lap(dt(2020, 6, 8, 10, 15, 0)), minutes(15));
+    assert!(timer.pause(dt(2020, 6, 8, 10, 20, 0)));
+    assert!(!timer.pause(dt(2020, 6, 8, 10, 25, 0)));
+    assert!(!timer.is_running());
+    assert_eq!(timer.elapsed(dt(2020, 6, 8, 11, 0, 0)), minutes(20));
+
+    assert!(timer.resume(dt(2020, 6, 8, 11, 0, 0)));
+    assert!(!timer.resume(dt(2020, 6, 8, 11, 5, 0)));
+    assert_eq!(timer.lap(dt(2020, 6, 8, 11, 10, 0)), minutes(15));
+    assert_eq!(timer.laps(), vec![minutes(15), minutes(15)]);
+    assert_eq!(timer.elapsed(dt(2020, 6, 8, 11, 30, 0)), minutes(50));
+    // Earlier points in time only count what had happened by then
+    assert_eq!(timer.elapsed(dt(2020, 6, 8, 10, 10, 0)), minutes(10));
+}
+
+#[test]
+fn test_timers_file_is_parsed_and_written() {
+    let text = "qa\tstart\t2020-06-08T10:00:00+03:00\n\
+                qa\tpause\t2020-06-08T10:30:00+03:00\n\
+                incident\tstart\t2020-06-08T09:00:00+00:00\n";
+    let timers = parse_timers(text).unwrap();
+
+    let qa = timers.get("qa").unwrap();
+    assert!(!qa.is_running());
+    assert_eq!(
+        qa.elapsed(dt(2020, 6, 9, 0, 0, 0)),
+        chrono::Duration::minutes(30)
+    );
+    assert!(timers.get("incident").unwrap().is_running());
+    assert_eq!(parse_timers(&timers.to_file()).unwrap(), timers);
+
+    let mut timers = Timers::default();
+    timers.insert("qa", Timer::start(dt(2020, 6, 8, 10, 0, 0)));
+    assert!(timers.remove("qa").is_some());
+    assert_eq!(timers.to_file(), "");
+
+    assert!(parse_timers("qa\tstart").is_err());
+    assert!(parse_timers("qa\tstop\t2020-06-08T10:00:00Z").is_err());
+    assert!(parse_timers("qa\tpause\t2020-06-08T10:00:00Z").is_err());
+    assert!(
+        parse_timers("qa\tstart\t2020-06-08T10:00:00Z\nqa\tstart\t2020-06-08T11:00:00Z").is_err()
+    );
+}
+
+//
+// CALCULATIONS & OUTPUT
+//
+#[test]
+fn test_month_and_year_diffs_count_full_anniversaries() {
+    let new_years_eve = dt(2019, 12, 31, 12, 0, 0);
+
+    assert_eq!(
+        calculate_month_diff(new_years_eve, dt(2020, 1, 1, 12, 0, 0)),
+        0
+    );
+    assert_eq!(
+        calculate_year_diff(new_years_eve, dt(2020, 1, 1, 12, 0, 0)),
+        0
+    );
+    assert_eq!(
+        calculate_month_diff(new_years_eve, dt(2020, 1, 31, 11, 59, 59)),
+        0
+    );
+    assert_eq!(
+        calculate_month_diff(new_years_eve, dt(2020, 1, 31, 12, 0, 0)),
+        1
+    );
+    assert_eq!(
+        calculate_year_diff(new_years_eve, dt(2020, 12, 31, 12, 0, 0)),
+        1
+    );
+
+    // Same results backwards
+    assert_eq!(
+        calculate_month_diff(dt(2020, 1, 31, 12, 0, 0), new_years_eve),
+        1
+    );
+    assert_eq!(
+        calculate_year_diff(dt(2020, 12, 31, 11, 0, 0), new_years_eve),
+        0
+    );
+}
+
+#[test]
+fn test_month_ends_are_clamped() {
+    // Jan 31st + 1 month == Feb 29th on a leap year
+    assert_eq!(
+        calculate_month_diff(dt(2020, 1, 31, 0, 0, 0), dt(2020, 2, 28, 0, 0, 0)),
+        0
+    );
+    assert_eq!(
+        calculate_month_diff(dt(2020, 1, 31, 0, 0, 0), dt(2020, 2, 29, 0, 0, 0)),
+        1
+    );
+    // Leap day birthdays are celebrated on Feb 28th
+    assert_eq!(
+        calculate_year_diff(dt(2020, 2, 29, 0, 0, 0), dt(2021, 2, 28, 0, 0, 0)),
+        1
+    );
+}
+
+#[test]
+fn test_breakdown_counts_calendar_months_and_remainder() {
+    assert_eq!(
+        calculate_breakdown(dt(2019, 1, 31, 12, 0, 0), dt(2020, 3, 1, 13, 15, 30)),
+        Breakdown {
+            years: 1,
+            months: 1,
+            days: 1,
+            hours: 1,
+            minutes: 15,
+            seconds: 30,
+        }
+    );
+
+    // Order of the arguments doesn't matter
+    assert_eq!(
+        calculate_breakdown(dt(2020, 3, 1, 0, 0, 0), dt(2020, 2, 29, 23, 0, 0)),
+        Breakdown {
+            hours: 1,
+            ..Breakdown::default()
+        }
+    );
+}
+
+#[test]
+fn test_filters_are_read_from_subcommand_names() {
+    assert_eq!(Filter::from_str("weeks"), Filter::Weeks);
+    assert_eq!(Filter::from_str("w"), Filter::Weeks);
+    assert_eq!(Filter::from_str("s"), Filter::Seconds);
+    assert_eq!(Filter::from_str("mi"), Filter::Minutes);
+    assert_eq!(
+        Filter::from_str(Filter::Workhours.as_str()),
+        Filter::Workhours
+    );
+    assert_eq!(Filter::from_str("add"), Filter::None);
+}
+
+#[test]
+fn test_full_output_lists_every_non_zero_unit() {
+    let defaults = OutputOptions::default();
+    let from = dt(2019, 4, 5, 9, 0, 0);
+
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 14, 15, 0), Filter::Full, &defaults),
+        "1 year, 2 months, 3 days, 5 hours and 15 minutes"
+    );
+    assert_eq!(
+        get_output(from, dt(2019, 4, 6, 9, 0, 1), Filter::Full, &defaults),
+        "1 day and 1 second"
+    );
+    assert_eq!(get_output(from, from, Filter::Full, &defaults), "0 seconds");
+}
+
+#[test]
+fn test_decimal_output_uses_real_calendar_lengths() {
+    let decimals = OutputOptions {
+        decimals: Some(2),
+        ..OutputOptions::default()
+    };
+    let from = dt(2020, 2, 1, 7, 0, 0);
+
+    assert_eq!(
+        get_output(from, dt(2020, 2, 1, 10, 12, 0), Filter::Hours, &decimals),
+        "3.20"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 2, 1, 10, 12, 0), Filter::None, &decimals),
+        "3.20 hours"
+    );
+    // Half of February vs. half of March
+    assert_eq!(
+        get_output(from, dt(2020, 2, 15, 19, 0, 0), Filter::Months, &decimals),
+        "0.50"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 3, 16, 19, 0, 0), Filter::Months, &decimals),
+        "1.50"
+    );
+    // Leap year, so 366 / 2 days is half a year
+    assert_eq!(
+        get_output(from, dt(2020, 8, 2, 7, 0, 0), Filter::Years, &decimals),
+        "0.50"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 2, 1, 7, 0, 1), Filter::Full, &decimals),
+        "1.00 seconds"
+    );
+    // The year after the last supported date doesn't exist, so it's an average one instead
+    assert_eq!(
+        get_output(
+            dt(2020, 12, 1, 0, 0, 0),
+            dt(262143, 12, 1, 0, 0, 0),
+            Filter::Years,
+            &decimals
+        ),
+        "260123.00"
+    );
+}
+
+#[test]
+fn test_signed_output_keeps_direction() {
+    let signed = OutputOptions {
+        signed: true,
+        ..OutputOptions::default()
+    };
+    let past = dt(2020, 6, 5, 10, 0, 0);
+    let future = dt(2020, 6, 11, 10, 0, 0);
+    let now = dt(2020, 6, 8, 10, 0, 0);
+
+    assert_eq!(get_output(past, now, Filter::Days, &signed), "3");
+    assert_eq!(get_output(future, now, Filter::Days, &signed), "-3");
+    assert_eq!(get_output(future, now, Filter::Months, &signed), "0");
+    assert_eq!(get_output(past, now, Filter::None, &signed), "3 days ago");
+    assert_eq!(get_output(future, now, Filter::None, &signed), "in 3 days");
+    assert_eq!(get_output(future, now, Filter::Full, &signed), "in 3 days");
+
+    let decimals = OutputOptions {
+        decimals: Some(1),
+        signed: true,
+        ..OutputOptions::default()
+    };
+    assert_eq!(get_output(future, now, Filter::Weeks, &decimals), "-0.4");
+}
+
+#[test]
+fn test_countdown_output_is_phrased_towards_target() {
+    let countdown = OutputOptions {
+        countdown: true,
+        ..OutputOptions::default()
+    };
+    let now = dt(2020, 6, 8, 10, 0, 0);
+
+    assert_eq!(
+        get_output(now, dt(2020, 6, 8, 12, 30, 0), Filter::None, &countdown),
+        "2 hours and 30 minutes left"
+    );
+    assert_eq!(
+        get_output(now, dt(2020, 6, 5, 10, 0, 0), Filter::None, &countdown),
+        "3 days ago"
+    );
+    assert_eq!(
+        get_output(now, dt(2020, 6, 5, 10, 0, 0), Filter::Days, &countdown),
+        "3"
+    );
+}
+
+#[test]
+fn test_shorthand_output_goes_down_to_seconds() {
+    let defaults = OutputOptions::default();
+    let from = dt(2020, 6, 8, 10, 0, 0);
+
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 10, 0, 20), Filter::None, &defaults),
+        "20 seconds"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 10, 12, 20), Filter::None, &defaults),
+        "12 minutes and 20 seconds"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 10, 1, 1), Filter::None, &defaults),
+        "1 minute and 1 second"
+    );
+    assert_eq!(
+        get_output(
+            from,
+            from + chrono::Duration::milliseconds(500),
+            Filter::None,
+            &defaults
+        ),
+        "500 milliseconds"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 12, 30, 20), Filter::None, &defaults),
+        "2 hours and 30 minutes"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 11, 1, 0), Filter::None, &defaults),
+        "1 hour and 1 minute"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 11, 0, 20), Filter::None, &defaults),
+        "1 hour"
+    );
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 10, 30, 0), Filter::None, &defaults),
+        "30 minutes"
+    );
+    assert_eq!(get_output(from, from, Filter::None, &defaults), "0 seconds");
+
+    let decimals = OutputOptions {
+        decimals: Some(1),
+        ..OutputOptions::default()
+    };
+    assert_eq!(
+        get_output(from, dt(2020, 6, 8, 9, 59, 40), Filter::None, &decimals),
+        "20.0 seconds"
+    );
+}
+
+#[test]
+fn test_sub_second_output_keeps_the_fraction() {
+    let defaults = OutputOptions::default();
+    let from = dt(2020, 6, 8, 10, 0, 0);
+    let to = from + chrono::Duration::nanoseconds(1_234_567_891);
+
+    assert_eq!(get_output(from, to, Filter::Seconds, &defaults), "1");
+    assert_eq!(
+        get_output(from, to, Filter::Milliseconds, &defaults),
+        "1234"
+    );
+    assert_eq!(
+        get_output(to, from, Filter::Microseconds, &defaults),
+        "1234567"
+    );
+    assert_eq!(
+        get_output(from, to, Filter::Nanoseconds, &defaults),
+        "1234567891"
+    );
+    // Far enough apart to overflow nanoseconds in 64 bits
+    assert_eq!(
+        get_output(
+            dt(1700, 1, 1, 0, 0, 0),
+            from,
+            Filter::Nanoseconds,
+            &defaults
+        ),
+        "10111946400000000000"
+    );
+
+    let decimals = OutputOptions {
+        decimals: Some(2),
+        ..OutputOptions::default()
+    };
+    assert_eq!(
+        get_output(from, to, Filter::Milliseconds, &decimals),
+        "1234.57"
+    );
+}
+
+#[test]
+fn test_difference_accessors_are_signed() {
+    let from = dt(2019, 4, 5, 9, 0, 0);
+    let to = dt(2020, 6, 8, 14, 15, 0);
+
+    let forward = Difference::new(from, to);
+    assert!(!forward.is_negative());
+    assert_eq!(forward.years(), 1);
+    assert_eq!(forward.months(), 14);
+    assert_eq!(forward.weeks(), 61);
+    assert_eq!(forward.days(), 430);
+    assert_eq!(forward.hours(), 10325);
+    assert_eq!(forward.shorthand(), "14 months");
+
+    let backward = Difference::new(to, from);
+    assert!(backward.is_negative());
+    assert_eq!(backward.years(), -1);
+    assert_eq!(backward.months(), -14);
+    assert_eq!(backward.minutes(), -619515);
+    assert_eq!(backward.breakdown(), forward.breakdown());
+    assert_eq!(backward.shorthand(), "14 months");
+}
+
+#[test]
+fn test_difference_between_timezones_uses_the_real_span() {
+    let from = Zone::Fixed(chrono::FixedOffset::east(2 * 60 * 60))
+        .ymd(2020, 2, 1)
+        .and_hms(0, 30, 0);
+    let to = Zone::Fixed(chrono::FixedOffset::east(0))
+        .ymd(2020, 1, 31)
+        .and_hms(23, 0, 0);
+
+    let difference = Difference::new(from, to);
+    assert_eq!(difference.months(), 0);
+    assert_eq!(difference.minutes(), 30);
+    assert_eq!(
+        difference.breakdown(),
+        Breakdown {
+            minutes: 30,
+            ..Breakdown::default()
+        }
+    );
+    assert_eq!(difference.iso8601(), "PT30M");
+    assert_eq!(
+        calculate_units(from, to, &[Filter::Months, Filter::Minutes]),
+        vec![0, 30]
+    );
+}
+
+#[test]
+fn test_json_output_contains_every_unit() {
+    let utc = Zone::Fixed(chrono::FixedOffset::east(0));
+    let from = utc.ymd(2020, 6, 8).and_hms(12, 30, 0);
+    let to = utc.ymd(2020, 6, 5).and_hms(10, 0, 0);
+
+    assert_eq!(
+        get_json_output(&Difference::new(from, to)),
+        concat!(
+            r#"{"from":"2020-06-08T12:30:00+00:00","to":"2020-06-05T10:00:00+00:00","negative":true,"#,
+            r#""difference":{"years":0,"months":0,"weeks":0,"days":-3,"hours":-74,"minutes":-4470,"seconds":-268200,"#,
+            r#""milliseconds":-268200000,"microseconds":-268200000000,"nanoseconds":-268200000000000},"#,
+            r#""breakdown":{"years":0,"months":0,"days":3,"hours":2,"minutes":30,"seconds":0},"#,
+            r#""iso8601":"-P3DT2H30M","shorthand":"3 days"}"#,
+        )
+    );
+}
+
+#[test]
+fn test_template_output_fills_out_only_the_given_units() {
+    let from = dt(2019, 1, 15, 10, 0, 0);
+    let to = dt(2020, 6, 9, 12, 30, 5);
+    let template = |template| {
+        let template = parse_template(template).unwrap();
+        get_template_output(from, to, &template, &OutputOptions::default())
+    };
+
+    assert_eq!(template("{y}y {mo}mo {d}d"), "1y 4mo 25d");
+    assert_eq!(template("{mo} months"), "16 months");
+    assert_eq!(template("{d}d {h}h {m}m"), "511d 2h 30m");
+    assert_eq!(template("{w}w {d}d"), "73w 0d");
+    assert_eq!(template("{0h}:{0m}:{0s}"), "12266:30:05");
+    assert_eq!(template("%d days, %H:%M:%S"), "511 days, 02:30:05");
+    assert_eq!(template("%-d.%-H"), "511.2");
+    assert_eq!(template("{{{m}}} %%"), "{735990} %");
+    assert_eq!(template("no fields"), "no fields");
+
+    // Direction is only shown when signed.
+    let template = parse_template("%H:%M").unwrap();
+    let signed = OutputOptions {
+        signed: true,
+        ..OutputOptions::default()
+    };
+    assert_eq!(
+        get_template_output(to, from, &template, &OutputOptions::default()),
+        "12266:30"
+    );
+    assert_eq!(
+        get_template_output(to, from, &template, &signed),
+        "-12266:30"
+    );
+
+    assert!(parse_template("{x}").is_err());
+    assert!(parse_template("{h").is_err());
+    assert!(parse_template("h}").is_err());
+    assert!(parse_template("%Q").is_err());
+    assert!(parse_template("%").is_err());
+}
+
+#[test]
+fn test_clock_output_includes_seconds_and_days() {
+    let from = dt(2020, 6, 8, 7, 0, 0);
+    let clock = |to, options: &OutputOptions| get_output(from, to, Filter::Clock, options);
+    let none = OutputOptions::default();
+
+    assert_eq!(clock(dt(2020, 6, 8, 10, 12, 7), &none), "03:12:07");
+    assert_eq!(clock(dt(2020, 6, 8, 7, 0, 0), &none), "00:00:00");
+    assert_eq!(clock(dt(2020, 6, 10, 10, 12, 7), &none), "2-03:12:07");
+    assert_eq!(clock(dt(2020, 6, 7, 6, 59, 59), &none), "1-00:00:01");
+
+    let millis = OutputOptions {
+        decimals: Some(3),
+        ..OutputOptions::default()
+    };
+    let to = dt(2020, 6, 8, 10, 12, 7) + chrono::Duration::microseconds(999_999);
+    assert_eq!(clock(to, &millis), "03:12:07.999");
+    assert_eq!(
+        clock(
+            to,
+            &OutputOptions {
+                decimals: Some(0),
+                ..millis
+            }
+        ),
+        "03:12:07"
+    );
+
+    let signed = OutputOptions {
+        signed: true,
+        ..OutputOptions::default()
+    };
+    assert_eq!(clock(dt(2020, 6, 8, 5, 0, 0), &signed), "-02:00:00");
+}
+
+#[test]
+fn test_iso_output_follows_the_calendar_breakdown() {
+    let from = dt(2019, 4, 5, 6, 7, 8);
+    let iso = |to, options: &OutputOptions| get_iso_output(from, to, options);
+    let none = OutputOptions::default();
+
+    assert_eq!(iso(dt(2020, 6, 8, 10, 12, 14), &none), "P1Y2M3DT4H5M6S");
+    assert_eq!(iso(dt(2019, 4, 12, 6, 7, 8), &none), "P7D");
+    assert_eq!(iso(dt(2019, 4, 5, 6, 7, 38), &none), "PT30S");
+    assert_eq!(iso(dt(2020, 4, 5, 7, 7, 8), &none), "P1YT1H");
+    assert_eq!(iso(from, &none), "PT0S");
+
+    let decimal = OutputOptions {
+        decimals: Some(2),
+        ..OutputOptions::default()
+    };
+    let to = dt(2019, 4, 5, 6, 7, 15) + chrono::Duration::milliseconds(250);
+    assert_eq!(iso(to, &decimal), "PT7.25S");
+
+    let signed = OutputOptions {
+        signed: true,
+        ..OutputOptions::default()
+    };
+    assert_eq!(iso(dt(2019, 4, 4, 6, 7, 8), &signed), "-P1D");
+    assert_eq!(iso(dt(2019, 4, 4, 6, 7, 8), &none), "P1D");
+
+    assert_eq!(
+        Difference::new(from, dt(2019, 3, 5, 6, 7, 8)).iso8601(),
+        "-P1M"
+    );
+}
+
+#[test]
+fn test_work_output_skips_nights_weekends_and_holidays() {
+    let calendar = WorkCalendar {
+        // Wednesday
+        holidays: vec![NaiveDate::from_ymd(2020, 6, 10)],
+        ..WorkCalendar::default()
+    };
+    let options = OutputOptions {
+        calendar,
+        ..OutputOptions::default()
+    };
+    let work = |from, to, filter| get_output(from, to, filter, &options);
+
+    // Friday afternoon to Monday lunch
+    let friday = dt(2020, 6, 5, 10, 0, 0);
+    let monday = dt(2020, 6, 8, 12, 30, 0);
+    assert_eq!(work(friday, monday, Filter::Workhours), "10");
+    assert_eq!(work(friday, monday, Filter::Workdays), "1");
+    assert_eq!(work(monday, friday, Filter::Workhours), "10");
+
+    // Outside of working hours
+    assert_eq!(
+        work(
+            dt(2020, 6, 5, 17, 0, 0),
+            dt(2020, 6, 8, 9, 0, 0),
+            Filter::Workhours
+        ),
+        "0"
+    );
+    // Two weeks minus the holiday
+    assert_eq!(
+        work(
+            dt(2020, 6, 1, 9, 0, 0),
+            dt(2020, 6, 15, 9, 0, 0),
+            Filter::Workdays
+        ),
+        "9"
+    );
+
+    let decimal = OutputOptions {
+        decimals: Some(2),
+        signed: true,
+        calendar: WorkCalendar {
+            start: NaiveTime::from_hms(8, 0, 0),
+            end: NaiveTime::from_hms(16, 0, 0),
+            ..WorkCalendar::default()
+        },
+        ..OutputOptions::default()
+    };
+    assert_eq!(
+        get_output(friday, monday, Filter::Workhours, &decimal),
+        "10.50"
+    );
+    assert_eq!(
+        get_output(monday, friday, Filter::Workdays, &decimal),
+        "-1.31"
+    );
+}
+
+#[test]
+fn test_work_hours_and_holidays_are_parsed() {
+    assert_eq!(
+        try_parse_work_hours("09:00-17:00"),
+        Ok((NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 0, 0)))
+    );
+    assert_eq!(
+        try_parse_work_hours("8-16:30"),
+        Ok((NaiveTime::from_hms(8, 0, 0), NaiveTime::from_hms(16, 30, 0)))
+    );
+    assert!(try_parse_work_hours("17-9").is_err());
+    assert!(try_parse_work_hours("9:00").is_err());
+    assert!(try_parse_work_hours("9-25").is_err());
+
+    let now = dt(2020, 6, 8, 10, 12, 14);
+    let options = ParseOptions::default();
+    let text = "# Midsummer\n2020-06-19\n\n20.6.2020  # Saturday anyway\n";
+    assert_eq!(
+        parse_holidays(text, now, &options).unwrap(),
+        vec![
+            NaiveDate::from_ymd(2020, 6, 19),
+            NaiveDate::from_ymd(2020, 6, 20)
+        ]
+    );
+
+    let err = parse_holidays("2020-06-19\nmidsummer\n", now, &options).unwrap_err();
+    assert!(err.to_string().starts_with("line 2: "));
+}
+
+#[test]
+fn test_watch_ticks_line_up_with_the_target() {
+    let target = dt(2020, 6, 8, 17, 0, 0);
+    let second = chrono::Duration::seconds(1);
+    let millis = chrono::Duration::milliseconds;
+
+    // Counting down and up
+    assert_eq!(
+        until_next_tick(target - millis(2300), target, second),
+        millis(300)
+    );
+    assert_eq!(
+        until_next_tick(target + millis(2300), target, second),
+        millis(700)
+    );
+    // Right on a tick waits for the next one
+    assert_eq!(until_next_tick(target - second, target, second), second);
+    assert_eq!(
+        until_next_tick(target - millis(4500), target, chrono::Duration::seconds(5)),
+        millis(4500)
+    );
+}
@@ -0,0 +1,246 @@
+use crate::errors::ConfigError;
+use crate::storage::{data_dir, replace_file};
+use crate::zones::Zone;
+use chrono::{DateTime, Duration, FixedOffset, Offset};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Something that happened to a timer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimerEvent {
+    Start,
+    Pause,
+    Resume,
+    Lap,
+}
+
+impl TimerEvent {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TimerEvent::Start => "start",
+            TimerEvent::Pause => "pause",
+            TimerEvent::Resume => "resume",
+            TimerEvent::Lap => "lap",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<TimerEvent> {
+        match s {
+            "start" => Some(TimerEvent::Start),
+            "pause" => Some(TimerEvent::Pause),
+            "resume" => Some(TimerEvent::Resume),
+            "lap" => Some(TimerEvent::Lap),
+            _ => None,
+        }
+    }
+}
+
+/// Stopwatch that survives between runs, kept as the list of everything that happened to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    events: Vec<(TimerEvent, DateTime<FixedOffset>)>,
+}
+
+impl Timer {
+    /// New timer, running from the given datetime.
+    pub fn start(at: DateTime<Zone>) -> Timer {
+        let mut timer = Timer { events: vec![] };
+        timer.push(TimerEvent::Start, at);
+        timer
+    }
+
+    /// Whether the timer is running, ie. not paused.
+    pub fn is_running(&self) -> bool {
+        !matches!(
+            self.events
+                .iter()
+                .rev()
+                .find(|(event, _)| *event != TimerEvent::Lap),
+            Some((TimerEvent::Pause, _)) | None
+        )
+    }
+
+    /// Stop counting until resumed. Returns `false` if the timer is already paused.
+    pub fn pause(&mut self, at: DateTime<Zone>) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+
+        self.push(TimerEvent::Pause, at);
+        true
+    }
+
+    /// Continue counting after a pause. Returns `false` if the timer isn't paused.
+    pub fn resume(&mut self, at: DateTime<Zone>) -> bool {
+        if self.is_running() {
+            return false;
+        }
+
+        self.push(TimerEvent::Resume, at);
+        true
+    }
+
+    /// Finish the current lap, returning its length.
+    pub fn lap(&mut self, at: DateTime<Zone>) -> Duration {
+        self.push(TimerEvent::Lap, at);
+        self.laps().pop().unwrap_or_else(Duration::zero)
+    }
+
+    /// Length of each finished lap, not counting the time spent paused.
+    pub fn laps(&self) -> Vec<Duration> {
+        let mut previous = Duration::zero();
+
+        self.events
+            .iter()
+            .filter(|(event, _)| *event == TimerEvent::Lap)
+            .map(|(_, at)| {
+                let total = self.elapsed_until(*at);
+                let lap = total - previous;
+                previous = total;
+                lap
+            })
+            .collect()
+    }
+
+    /// Total running time until the given datetime, not counting the time spent paused.
+    pub fn elapsed(&self, at: DateTime<Zone>) -> Duration {
+        self.elapsed_until(at.with_timezone(&at.offset().fix()))
+    }
+
+    fn elapsed_until(&self, at: DateTime<FixedOffset>) -> Duration {
+        let mut total = Duration::zero();
+        let mut running_since = None;
+
+        for (event, time) in self.events.iter().take_while(|(_, time)| *time <= at) {
+            match event {
+                TimerEvent::Start | TimerEvent::Resume => running_since = Some(*time),
+                TimerEvent::Pause => {
+                    if let Some(since) = running_since.take() {
+                        total = total + time.signed_duration_since(since);
+                    }
+                }
+                TimerEvent::Lap => {}
+            }
+        }
+
+        match running_since {
+            Some(since) => total + at.signed_duration_since(since),
+            None => total,
+        }
+    }
+
+    fn push(&mut self, event: TimerEvent, at: DateTime<Zone>) {
+        self.events
+            .push((event, at.with_timezone(&at.offset().fix())));
+    }
+}
+
+/// All the timers by name, stored in `~/.local/share/since/timers` by default.
+///
+/// The file has one event per line: the name of the timer, the event and an RFC 3339 datetime,
+/// separated by tabs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timers {
+    timers: BTreeMap<String, Timer>,
+}
+
+impl Timers {
+    pub fn get(&self, name: &str) -> Option<&Timer> {
+        self.timers.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Timer> {
+        self.timers.get_mut(name)
+    }
+
+    /// Add the timer, replacing any previous one with the same name.
+    pub fn insert(&mut self, name: &str, timer: Timer) {
+        self.timers.insert(name.to_string(), timer);
+    }
+
+    /// Remove the timer and return it, if there was one.
+    pub fn remove(&mut self, name: &str) -> Option<Timer> {
+        self.timers.remove(name)
+    }
+
+    /// Contents of the timers file, readable by `parse_timers`.
+    pub fn to_file(&self) -> String {
+        let mut text = String::new();
+
+        for (name, timer) in &self.timers {
+            for (event, at) in &timer.events {
+                text.push_str(&format!(
+                    "{}\t{}\t{}\n",
+                    name,
+                    event.as_str(),
+                    at.to_rfc3339()
+                ));
+            }
+        }
+
+        text
+    }
+}
+
+/// Location of the timers file.
+pub fn timers_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("timers"))
+}
+
+/// Read the timers from the given path. A missing file is the same as an empty one.
+pub fn load_timers(path: &Path) -> Result<Timers, ConfigError> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse_timers(&text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Timers::default()),
+        Err(err) => Err(ConfigError::Io(err)),
+    }
+}
+
+/// Write the timers into the given path, see `replace_file`.
+pub fn save_timers(path: &Path, timers: &Timers) -> io::Result<()> {
+    replace_file(path, &timers.to_file())
+}
+
+/// Parse the contents of a timers file.
+pub fn parse_timers(text: &str) -> Result<Timers, ConfigError> {
+    let mut timers = Timers::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let syntax_error = |reason: &str| ConfigError::Syntax {
+            line: index + 1,
+            reason: reason.to_string(),
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (name, event, at) = match fields[..] {
+            [name, event, at] => (name, event, at),
+            _ => return Err(syntax_error("expected a name, an event and a datetime")),
+        };
+
+        let event = TimerEvent::from_str(event)
+            .ok_or_else(|| syntax_error(&format!("unknown event `{}`", event)))?;
+        let at = DateTime::parse_from_rfc3339(at.trim())
+            .map_err(|err| syntax_error(&format!("invalid datetime: {}", err)))?;
+
+        match timers.timers.get_mut(name) {
+            Some(timer) if event != TimerEvent::Start => timer.events.push((event, at)),
+            None if event == TimerEvent::Start => {
+                timers.insert(
+                    name,
+                    Timer {
+                        events: vec![(event, at)],
+                    },
+                );
+            }
+            _ => return Err(syntax_error("timers have to start exactly once")),
+        }
+    }
+
+    Ok(timers)
+}
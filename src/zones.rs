@@ -0,0 +1,141 @@
+use chrono::offset::LocalResult;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+
+/// Timezone used for parsing and calculations.
+///
+/// Wraps the different kinds of zones `since` understands into a single type, so the rest of the
+/// code can keep working with plain `DateTime<Zone>` values regardless of where they came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    /// The system's local timezone, default for everything.
+    Local,
+    /// Fixed UTC offset, eg. `Z` or `+03:00`.
+    Fixed(FixedOffset),
+    /// IANA timezone, eg. `Europe/Helsinki`.
+    Named(Tz),
+}
+
+/// Offset of a `Zone` at some specific moment.
+#[derive(Clone, Copy, Debug)]
+pub struct ZoneOffset {
+    zone: Zone,
+    offset: FixedOffset,
+}
+
+impl Zone {
+    /// Current datetime in this timezone.
+    pub fn now(&self) -> DateTime<Zone> {
+        Utc::now().with_timezone(self)
+    }
+
+    fn with_offset<O: Offset>(&self, offset: O) -> ZoneOffset {
+        ZoneOffset {
+            zone: *self,
+            offset: offset.fix(),
+        }
+    }
+}
+
+/// Parse a timezone from either an IANA name, `Z`, `UTC` or a UTC offset like `+03:00`, `+0300`
+/// or `-08`. `local` means the system timezone.
+pub fn parse_zone(arg: &str) -> Option<Zone> {
+    match arg {
+        "local" | "Local" => Some(Zone::Local),
+        "Z" | "z" => Some(Zone::Fixed(FixedOffset::east(0))),
+        _ => parse_offset(arg)
+            .map(Zone::Fixed)
+            .or_else(|| arg.parse::<Tz>().ok().map(Zone::Named)),
+    }
+}
+
+/// Parse a bare UTC offset: `+HH`, `+HHMM` or `+HH:MM`, or the same with a minus sign.
+fn parse_offset(arg: &str) -> Option<FixedOffset> {
+    let sign = match arg.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+
+    let digits = arg[1..].replacen(':', "", 1);
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (hours, minutes): (i32, i32) = match digits.len() {
+        2 => (digits.parse().ok()?, 0),
+        4 => (digits[..2].parse().ok()?, digits[2..].parse().ok()?),
+        _ => return None,
+    };
+
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 60 * 60 + minutes * 60))
+}
+
+impl Offset for ZoneOffset {
+    fn fix(&self) -> FixedOffset {
+        self.offset
+    }
+}
+
+impl fmt::Display for ZoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.offset.fmt(f)
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = ZoneOffset;
+
+    fn from_offset(offset: &ZoneOffset) -> Zone {
+        offset.zone
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<ZoneOffset> {
+        match self {
+            Zone::Local => Local
+                .offset_from_local_date(local)
+                .map(|o| self.with_offset(o)),
+            Zone::Fixed(fixed) => fixed
+                .offset_from_local_date(local)
+                .map(|o| self.with_offset(o)),
+            Zone::Named(tz) => tz
+                .offset_from_local_date(local)
+                .map(|o| self.with_offset(o)),
+        }
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<ZoneOffset> {
+        match self {
+            Zone::Local => Local
+                .offset_from_local_datetime(local)
+                .map(|o| self.with_offset(o)),
+            Zone::Fixed(fixed) => fixed
+                .offset_from_local_datetime(local)
+                .map(|o| self.with_offset(o)),
+            Zone::Named(tz) => tz
+                .offset_from_local_datetime(local)
+                .map(|o| self.with_offset(o)),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> ZoneOffset {
+        match self {
+            Zone::Local => self.with_offset(Local.offset_from_utc_date(utc)),
+            Zone::Fixed(fixed) => self.with_offset(fixed.offset_from_utc_date(utc)),
+            Zone::Named(tz) => self.with_offset(tz.offset_from_utc_date(utc)),
+        }
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        match self {
+            Zone::Local => self.with_offset(Local.offset_from_utc_datetime(utc)),
+            Zone::Fixed(fixed) => self.with_offset(fixed.offset_from_utc_datetime(utc)),
+            Zone::Named(tz) => self.with_offset(tz.offset_from_utc_datetime(utc)),
+        }
+    }
+}